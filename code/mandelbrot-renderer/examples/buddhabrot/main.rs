@@ -0,0 +1,36 @@
+//! Renders a Buddhabrot or Nebulabrot on the CPU, rewriting the image after every batch of samples,
+//! so that it can be watched converging in an image viewer.
+//!
+//! Usage: `buddhabrot <output.png> <width> <height> [buddhabrot | nebulabrot [batches]]`
+
+use mandelbrot_renderer::BuddhabrotRenderer;
+
+/// The number of samples per batch and pixel
+const SAMPLES_PER_PIXEL: u32 = 2;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() < 3 {
+        eprintln!("Usage: buddhabrot <output.png> <width> <height> [buddhabrot | nebulabrot [batches]]");
+        std::process::exit(1);
+    }
+
+    let output = &args[0];
+    let size: (u32, u32) = (args[1].parse().unwrap(), args[2].parse().unwrap());
+    let bands = match args.get(3).map(String::as_str) {
+        None | Some("buddhabrot") => BuddhabrotRenderer::BUDDHABROT_BANDS,
+        Some("nebulabrot") => BuddhabrotRenderer::NEBULABROT_BANDS,
+        Some(other) => panic!("Unknown mode `{other}`"),
+    };
+    let batches: u32 = args.get(4).map(|batches| batches.parse().unwrap()).unwrap_or(20);
+
+    // The whole set. Rotate the image by 90 degrees to see the "Buddha" sitting upright
+    let aspect_ratio = size.0 as f64 / size.1 as f64;
+    let mut buddhabrot = BuddhabrotRenderer::new((-0.5, 0.0), (1.5 * aspect_ratio, 1.5), size).with_bands(bands);
+
+    for batch in 1..=batches {
+        buddhabrot.add_samples(SAMPLES_PER_PIXEL * size.0 * size.1);
+        buddhabrot.save_png(output).unwrap();
+        println!("Batch {batch}/{batches}: {} samples", buddhabrot.samples());
+    }
+}
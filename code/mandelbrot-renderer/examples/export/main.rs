@@ -0,0 +1,65 @@
+//! Renders a large image without opening a window.
+//!
+//! Usage: `export <output.png | output.dzi | output.stl | output.obj | output.svg> <width> <height> [center_re center_im zoom_exponent [supersampling]]`
+//!
+//! STL and OBJ outputs are heightfield meshes with one vertex per pixel, SVG outputs are contours of the iteration bands.
+//! Set `PALETTE` to a `.ggr`, `.map` or `.ugr` file to use its colors, and `PALETTE_INTERPOLATION` to `linear-rgb`,
+//! `oklab`, `oklch` or `hsluv` to choose how they are blended.
+
+use mandelbrot_renderer::{
+    ContourExport, HeightfieldExport, MandelbrotRenderer, Palette, PaletteInterpolation, RendererConfig, TiledExport,
+    Viewport,
+};
+use pollster::FutureExt;
+
+fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() < 3 {
+        eprintln!("Usage: export <output.png | output.dzi | output.stl | output.obj | output.svg> <width> <height> [center_re center_im zoom_exponent [supersampling]]");
+        std::process::exit(1);
+    }
+
+    let arg = |index: usize, default: &str| args.get(index).map(String::as_str).unwrap_or(default).to_owned();
+    let output = std::path::PathBuf::from(&args[0]);
+    let size = (args[1].parse().unwrap(), args[2].parse().unwrap());
+    let viewport = Viewport::new(
+        (arg(3, "-0.5").parse().unwrap(), arg(4, "0").parse().unwrap()),
+        arg(5, "0").parse().unwrap(),
+    );
+    let supersampling = arg(6, "2").parse().unwrap();
+    let palette = std::env::var("PALETTE")
+        .map(|path| Palette::load(path).unwrap())
+        .unwrap_or_default();
+    let palette = match std::env::var("PALETTE_INTERPOLATION") {
+        Ok(name) => palette.with_interpolation(PaletteInterpolation::from_name(&name).unwrap()),
+        Err(_) => palette,
+    };
+
+    // Any adapter will do without a window, including OpenGL ones on machines without Vulkan
+    let backends = wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::all());
+    let config = RendererConfig::new()
+        .with_backends(backends)
+        .with_palette(palette.clone());
+    let mut mandelbrot = MandelbrotRenderer::new_headless(config).block_on().unwrap();
+
+    let extension = output.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    if extension == "stl" || extension == "obj" || extension == "svg" {
+        mandelbrot.render(viewport.center, viewport.camera_size(size));
+        let data = mandelbrot.capture_iteration_data(size).block_on().unwrap();
+        if extension == "svg" {
+            ContourExport::new().with_palette(palette).save_svg(&data, &output).unwrap();
+        } else {
+            HeightfieldExport::new().mesh(&data).unwrap().save(&output).unwrap();
+        }
+        return;
+    }
+
+    let export = TiledExport::new(viewport, size).with_supersampling(supersampling);
+    if extension == "dzi" {
+        export.save_dzi(&mut mandelbrot, &output).block_on().unwrap();
+    } else {
+        export.save_png(&mut mandelbrot, &output).block_on().unwrap();
+    }
+}
@@ -1,11 +1,22 @@
-use std::sync::Arc;
+use std::{convert::Infallible, sync::Arc};
 
 use cgmath::{ElementWise, Vector2, Zero};
-use mandelbrot_renderer::MandelbrotRenderer;
+use embedded_graphics::{
+    mono_font::{ascii::FONT_8X13, MonoTextStyle},
+    pixelcolor::Rgb888,
+    prelude::*,
+    text::{Baseline, Text},
+};
+use mandelbrot_renderer::{
+    read_png_view_descriptor, Coloring, FpsCounter, Fractal, HeightfieldExport, Hud, KfrLocation, MandelbrotRenderer,
+    Overlay, Palette, Navigation, NavigationSteps, PaletteInterpolation, QualityPolicy, RendererConfig, StepFunction,
+    Viewport,
+};
 use pollster::FutureExt;
-use web_time::{Duration, Instant};
-use winit::event::{ElementState, Event, MouseButton, MouseScrollDelta, WindowEvent};
+use web_time::Duration;
+use winit::event::{ElementState, Event, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent};
 use winit::event_loop::EventLoop;
+use winit::keyboard::{Key, KeyCode, NamedKey, PhysicalKey};
 use winit::window::Window;
 
 fn main() {
@@ -13,17 +24,75 @@ fn main() {
     let window = Arc::new(Window::new(&event_loop).unwrap());
 
     let window_size = (window.inner_size().width, window.inner_size().height);
-    let mut mandelbrot = MandelbrotRenderer::new(window_size, window.clone(), false)
+    // Drop to half the resolution while dragging or zooming, and further if frames take longer than 30 FPS allow
+    let quality_policy = QualityPolicy::new()
+        .with_interaction_scale(0.5)
+        .with_target_frame_time(Duration::from_millis(33));
+    let config = RendererConfig::new()
+        .with_vsync(false)
+        .with_quality_policy(quality_policy);
+    let mut mandelbrot = MandelbrotRenderer::new(window_size, window.clone(), config)
         .block_on()
         .unwrap();
 
     let mut fps = FpsCounter::new();
+    let mut last_fps = None;
+    let mut title = String::new();
+    // Toggled with F9, and drawn into the top left corner of the window
+    let mut show_hud = false;
+    // The lines of the HUD that is currently drawn, to only rasterize it again when they change
+    let mut hud_lines = Vec::new();
 
     let mut is_mouse_down: bool = false;
     let mut previous_mouse_position: Option<Vector2<f32>> = None;
 
     let mut camera_position: Vector2<f32> = Vector2::zero();
     let mut camera_size_exponent = 0.0;
+    let mut palette_interpolation = PaletteInterpolation::default();
+
+    // Optionally start at the view stored in a screenshot or Kalles Fraktaler location
+    if let Some(path) = std::env::args().nth(1) {
+        let view = if path.ends_with(".kfr") {
+            let location = KfrLocation::load(&path).unwrap();
+            mandelbrot.set_palette(location.palette().unwrap());
+            location.to_view_descriptor(window_size.0 as f32 / window_size.1 as f32).unwrap()
+        } else {
+            let file = std::io::BufReader::new(std::fs::File::open(&path).unwrap());
+            read_png_view_descriptor(file).unwrap()
+        };
+
+        camera_position = Vector2::from(view.viewport.center);
+        camera_size_exponent = -view.viewport.zoom_exponent / 1.1_f32.log10();
+        mandelbrot.set_shader_constants(view.shader_constants);
+        mandelbrot.set_fractal(view.fractal);
+        mandelbrot.set_polynomial(view.polynomial);
+        mandelbrot.set_step_function(view.step_function);
+        palette_interpolation = view.palette_interpolation;
+        let palette = mandelbrot.palette().clone();
+        mandelbrot.set_palette(palette.with_interpolation(palette_interpolation));
+    }
+
+    // The view the reset key returns to
+    let home = Viewport::new(camera_position.into(), -camera_size_exponent * 1.1_f32.log10());
+
+    // Optionally load the colors of a `.ggr`, `.map` or `.ugr` gradient file, blended like the loaded view
+    if let Ok(path) = std::env::var("PALETTE") {
+        match Palette::load(&path) {
+            Ok(palette) => mandelbrot.set_palette(palette.with_interpolation(palette_interpolation)),
+            Err(err) => eprintln!("Failed to load palette: {err:#}"),
+        }
+    }
+
+    // Optionally load the body of a custom step function, e.g. `return complex_reciprocal(z * z + c);`
+    if let Ok(path) = std::env::var("STEP_FUNCTION") {
+        match StepFunction::new(std::fs::read_to_string(&path).unwrap()) {
+            Ok(step_function) => {
+                mandelbrot.set_step_function(Some(step_function));
+                mandelbrot.set_fractal(Fractal::Custom);
+            }
+            Err(diagnostic) => eprintln!("Invalid step function in {path}: {diagnostic}"),
+        }
+    }
 
 
     event_loop
@@ -53,16 +122,221 @@ fn main() {
                             delta.mul_assign_element_wise(calc_camera_size(&window, camera_size_exponent));
 
                             camera_position += delta;
+                            mandelbrot.mark_interaction();
                         }
                     }
 
                     previous_mouse_position = Some(position);
                 }
+                WindowEvent::CursorLeft { .. } => {
+                    previous_mouse_position = None;
+                }
                 WindowEvent::MouseWheel {
                     delta: MouseScrollDelta::LineDelta(_, delta),
                     ..
                 } => {
+                    // The point on the complex plane that is currently under the cursor
+                    let cursor = previous_mouse_position
+                        .map(|position| window_to_camera_coordinates(&window, position))
+                        .unwrap_or(Vector2::zero());
+                    let cursor_position = camera_position + cursor.mul_element_wise(calc_camera_size(&window, camera_size_exponent));
+
                     camera_size_exponent -= delta;
+
+                    // Move the camera so that the same point stays under the cursor
+                    camera_position = cursor_position - cursor.mul_element_wise(calc_camera_size(&window, camera_size_exponent));
+                    mandelbrot.mark_interaction();
+                }
+                WindowEvent::KeyboardInput {
+                    event: KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::F2),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                    ..
+                } => {
+                    let frame = mandelbrot.capture_frame().block_on().unwrap();
+                    let timestamp = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs();
+                    let path = format!("mandelbrot-{timestamp}.png");
+
+                    match frame.save_png(&path) {
+                        Ok(()) => println!("Saved screenshot to {path}"),
+                        Err(err) => eprintln!("Failed to save screenshot: {err}"),
+                    }
+                }
+                WindowEvent::KeyboardInput {
+                    event: KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::F3),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                    ..
+                } => {
+                    let size = (window.inner_size().width, window.inner_size().height);
+                    let data = mandelbrot.capture_iteration_data(size).block_on().unwrap();
+                    let timestamp = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs();
+                    let path = format!("mandelbrot-{timestamp}");
+
+                    match data.save_raw(&path) {
+                        Ok(()) => println!("Saved iteration data to {path}.bin and {path}.json"),
+                        Err(err) => eprintln!("Failed to save iteration data: {err}"),
+                    }
+                }
+                WindowEvent::KeyboardInput {
+                    event: KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::F4),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                    ..
+                } => {
+                    // Cycles through the coloring modes
+                    let mut shader_constants = mandelbrot.shader_constants();
+                    shader_constants.coloring = match shader_constants.coloring {
+                        Coloring::EscapeTime => Coloring::BinaryDecomposition,
+                        Coloring::BinaryDecomposition => Coloring::FieldLines,
+                        Coloring::FieldLines => Coloring::EscapeTime,
+                    };
+                    mandelbrot.set_shader_constants(shader_constants);
+                    println!("Coloring: {}", shader_constants.coloring.name());
+                }
+                WindowEvent::KeyboardInput {
+                    event: KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::F5),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                    ..
+                } => {
+                    // Cycles through the fractals
+                    let fractal = match mandelbrot.fractal() {
+                        Fractal::Mandelbrot => Fractal::Newton,
+                        Fractal::Newton => Fractal::Custom,
+                        Fractal::Custom => Fractal::Mandelbrot,
+                    };
+                    mandelbrot.set_fractal(fractal);
+                    println!("Fractal: {}", fractal.name());
+                }
+                WindowEvent::KeyboardInput {
+                    event: KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::F6),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                    ..
+                } => {
+                    // One vertex per pixel of the window
+                    let size = (window.inner_size().width, window.inner_size().height);
+                    let data = mandelbrot.capture_iteration_data(size).block_on().unwrap();
+                    let timestamp = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs();
+                    let path = format!("mandelbrot-{timestamp}.stl");
+
+                    match HeightfieldExport::new().mesh(&data).and_then(|mesh| mesh.save(&path)) {
+                        Ok(()) => println!("Saved heightfield to {path}"),
+                        Err(err) => eprintln!("Failed to save heightfield: {err}"),
+                    }
+                }
+                WindowEvent::KeyboardInput {
+                    event: KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::F7),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                    ..
+                } => {
+                    let size = window.inner_size();
+                    let location = KfrLocation::from_view_descriptor(
+                        &mandelbrot.view_descriptor(),
+                        mandelbrot.palette(),
+                        size.width as f32 / size.height as f32,
+                    );
+                    let timestamp = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs();
+                    let path = format!("mandelbrot-{timestamp}.kfr");
+
+                    match location.save(&path) {
+                        Ok(()) => println!("Saved location to {path}"),
+                        Err(err) => eprintln!("Failed to save location: {err}"),
+                    }
+                }
+                WindowEvent::KeyboardInput {
+                    event: KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::F8),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                    ..
+                } => {
+                    // Cycles through the color spaces palette entries are blended in
+                    let palette = mandelbrot.palette().clone();
+                    let interpolation = match palette.interpolation() {
+                        PaletteInterpolation::LinearRgb => PaletteInterpolation::Oklab,
+                        PaletteInterpolation::Oklab => PaletteInterpolation::Oklch,
+                        PaletteInterpolation::Oklch => PaletteInterpolation::Hsluv,
+                        PaletteInterpolation::Hsluv => PaletteInterpolation::LinearRgb,
+                    };
+                    mandelbrot.set_palette(palette.with_interpolation(interpolation));
+                    println!("Palette interpolation: {}", interpolation.name());
+                }
+                WindowEvent::KeyboardInput {
+                    event: KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::F9),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                    ..
+                } => {
+                    show_hud = !show_hud;
+                    if !show_hud {
+                        mandelbrot.set_overlay(None);
+                        hud_lines.clear();
+                    }
+                }
+                WindowEvent::KeyboardInput {
+                    event: KeyEvent {
+                        logical_key,
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                    ..
+                } => {
+                    // The same keys as in the browser: arrows or WASD to pan, +/- to zoom and 0 or Home to reset
+                    let key = match &logical_key {
+                        Key::Named(NamedKey::ArrowLeft) => "ArrowLeft",
+                        Key::Named(NamedKey::ArrowRight) => "ArrowRight",
+                        Key::Named(NamedKey::ArrowUp) => "ArrowUp",
+                        Key::Named(NamedKey::ArrowDown) => "ArrowDown",
+                        Key::Named(NamedKey::Home) => "Home",
+                        Key::Character(character) => character.as_str(),
+                        _ => "",
+                    };
+
+                    if let Some(navigation) = Navigation::from_key(key) {
+                        let viewport = Viewport::new(camera_position.into(), -camera_size_exponent * 1.1_f32.log10())
+                            .navigate(navigation, NavigationSteps::default(), home);
+                        camera_position = Vector2::from(viewport.center);
+                        camera_size_exponent = -viewport.zoom_exponent / 1.1_f32.log10();
+                        mandelbrot.mark_interaction();
+                    }
                 }
                 WindowEvent::MouseInput { state, button, .. } => {
                     if button == MouseButton::Left {
@@ -71,10 +345,35 @@ fn main() {
                 }
                 WindowEvent::RedrawRequested => {
                     if let Some(fps) = fps.record_frame() {
-                        window.set_title(&format!("FPS: {fps:.1}"));
+                        last_fps = Some(fps);
+                    }
+
+                    let new_title = last_fps.map(|fps| format!("FPS: {fps:.1}")).unwrap_or_default();
+                    if new_title != title {
+                        window.set_title(&new_title);
+                        title = new_title;
                     }
 
                     let camera_size = calc_camera_size(&window, camera_size_exponent);
+                    if show_hud {
+                        let viewport = Viewport::new(camera_position.into(), -camera_size_exponent * 1.1_f32.log10());
+                        let mut hud = Hud::new(viewport, window.inner_size().width);
+                        if let Some(position) = previous_mouse_position {
+                            let pointer = camera_position + window_to_camera_coordinates(&window, position).mul_element_wise(camera_size);
+                            hud = hud.with_pointer((pointer.x as f64, pointer.y as f64), &mandelbrot.cpu_renderer());
+                        }
+                        if let Some(fps) = last_fps {
+                            hud = hud.with_fps(fps);
+                        }
+
+                        let lines = hud.lines();
+                        if lines != hud_lines {
+                            let scale = window.scale_factor().round().max(1.0) as u32;
+                            mandelbrot.set_overlay(Some(&hud_overlay(&lines, scale)));
+                            hud_lines = lines;
+                        }
+                    }
+
                     mandelbrot.render((camera_position.x, camera_position.y), (camera_size.x, camera_size.y));
                 }
                 _ => {}
@@ -88,39 +387,75 @@ fn main() {
 
 }
 
-fn calc_camera_size(window: &Window, camera_size_exponent: f32) -> Vector2<f32> {
-    let size = 1.1_f32.powf(camera_size_exponent);
+/// Rasterizes the lines of the HUD in white on a translucent black box, with every font pixel `scale` window pixels wide
+fn hud_overlay(lines: &[String], scale: u32) -> Overlay {
+    const PADDING: u32 = 4;
 
-    Vector2::new(size, size * window.inner_size().height as f32 / window.inner_size().width as f32)
+    let character_size = FONT_8X13.character_size;
+    let columns = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0) as u32;
+    let width = columns * character_size.width + 2 * PADDING;
+    let height = lines.len() as u32 * character_size.height + 2 * PADDING;
+
+    let mut overlay = Overlay::new(width * scale, height * scale).with_position((2 * PADDING * scale, 2 * PADDING * scale));
+    for pixel in overlay.pixels.chunks_exact_mut(4) {
+        pixel.copy_from_slice(&[0, 0, 0, 160]);
+    }
+
+    let mut canvas = HudCanvas { overlay, scale };
+    let style = MonoTextStyle::new(&FONT_8X13, Rgb888::WHITE);
+    for (i, line) in lines.iter().enumerate() {
+        let position = Point::new(PADDING as i32, (PADDING + i as u32 * character_size.height) as i32);
+        Text::with_baseline(line, position, style, Baseline::Top).draw(&mut canvas).unwrap();
+    }
+
+    canvas.overlay
 }
 
-/// Fps counter that calculates average FPS every second
-struct FpsCounter {
-    current_num_frames: usize,
-    last_calculation: Instant,
+/// Lets `embedded-graphics` draw text into an overlay, scaling every pixel up to a `scale` by `scale` square
+struct HudCanvas {
+    overlay: Overlay,
+    scale: u32,
 }
 
-impl FpsCounter {
-    pub fn new() -> Self {
-        Self {
-            current_num_frames: 0,
-            last_calculation: Instant::now(),
+impl OriginDimensions for HudCanvas {
+    fn size(&self) -> Size {
+        Size::new(self.overlay.width / self.scale, self.overlay.height / self.scale)
+    }
+}
+
+impl DrawTarget for HudCanvas {
+    type Color = Rgb888;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            let (Ok(x), Ok(y)) = (u32::try_from(point.x), u32::try_from(point.y)) else {
+                continue;
+            };
+            for dy in 0..self.scale {
+                for dx in 0..self.scale {
+                    let position = (x * self.scale + dx, y * self.scale + dy);
+                    self.overlay.set_pixel(position, [color.r(), color.g(), color.b(), 255]);
+                }
+            }
         }
+
+        Ok(())
     }
+}
 
-    // Returns newly calculated fps
-    pub fn record_frame(&mut self) -> Option<f32> {
-        self.current_num_frames += 1;
+/// Converts a position in window pixels into the -1..=1 coordinate system used by the shader
+fn window_to_camera_coordinates(window: &Window, position: Vector2<f32>) -> Vector2<f32> {
+    let window_size = Vector2::new(window.inner_size().width as f32, window.inner_size().height as f32);
 
-        let now = Instant::now();
-        let dt = now.duration_since(self.last_calculation);
+    Vector2::new(2.0 * position.x / window_size.x - 1.0, 1.0 - 2.0 * position.y / window_size.y)
+}
 
-        (dt > Duration::from_secs(1)).then(|| {
-            let dt_per_frame = 1.0 / (dt.as_secs_f32() * self.current_num_frames as f32);
-            self.current_num_frames = 0;
-            self.last_calculation = now;
+fn calc_camera_size(window: &Window, camera_size_exponent: f32) -> Vector2<f32> {
+    let size = 1.1_f32.powf(camera_size_exponent);
 
-            1.0 / dt_per_frame
-        })
-    }
+    Vector2::new(size, size * window.inner_size().height as f32 / window.inner_size().width as f32)
 }
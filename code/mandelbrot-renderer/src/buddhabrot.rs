@@ -0,0 +1,211 @@
+//! Buddhabrot and Nebulabrot rendering.
+//!
+//! Instead of coloring each point by its escape time, random points `c` are sampled, and the orbits of those that
+//! escape are traced. Every visit of an orbit to a pixel is counted, and the resulting density is the image.
+//! Each color channel only counts orbits that escape within its own band of iterations. If all bands are the same,
+//! the image is gray (Buddhabrot), otherwise the channels show structures of different depths (Nebulabrot).
+//!
+//! The density only converges slowly, so samples are added in batches, and the image can be shown after each batch.
+
+use std::{io::Write, ops::Range};
+
+use anyhow::Result;
+
+use crate::color::linear_to_srgb;
+
+/// Accumulates orbit densities on the CPU, see the [module documentation](self)
+#[derive(Clone, Debug)]
+pub struct BuddhabrotRenderer {
+    camera_center: (f64, f64),
+    camera_size: (f64, f64),
+    size: (u32, u32),
+    /// The escape iterations counted by the red, green and blue channel
+    bands: [Range<u32>; 3],
+    /// The number of orbit visits per pixel and channel, row by row starting at the top
+    histogram: Vec<[u32; 3]>,
+    samples: u64,
+    rng: XorShift,
+}
+
+impl BuddhabrotRenderer {
+    /// The iteration bands of a Buddhabrot: every orbit that escapes within 1000 iterations, in every channel
+    pub const BUDDHABROT_BANDS: [Range<u32>; 3] = [0..1000, 0..1000, 0..1000];
+    /// The classic Nebulabrot iteration bands: long orbits in red, medium ones in green and short ones in blue
+    pub const NEBULABROT_BANDS: [Range<u32>; 3] = [0..2000, 0..200, 0..50];
+
+    /// The camera works like the one of [`crate::MandelbrotRenderer::render`]
+    pub fn new(camera_center: (f64, f64), camera_size: (f64, f64), (width, height): (u32, u32)) -> Self {
+        Self {
+            camera_center,
+            camera_size,
+            size: (width, height),
+            bands: Self::BUDDHABROT_BANDS,
+            histogram: vec![[0; 3]; width as usize * height as usize],
+            samples: 0,
+            rng: XorShift::new(0x853c_49e6_748f_ea9b),
+        }
+    }
+
+    /// The escape iterations counted by the red, green and blue channel. Clears the accumulated samples.
+    pub fn with_bands(mut self, bands: [Range<u32>; 3]) -> Self {
+        self.bands = bands;
+        self.clear();
+        self
+    }
+
+    /// Seeds the random number generator picking the samples, so that renders can be reproduced
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = XorShift::new(seed);
+        self
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn bands(&self) -> &[Range<u32>; 3] {
+        &self.bands
+    }
+
+    /// The number of points sampled so far, including those that did not escape
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    /// The number of orbit visits per pixel for the red, green and blue channel, row by row starting at the top
+    pub fn histogram(&self) -> &[[u32; 3]] {
+        &self.histogram
+    }
+
+    /// Discards the accumulated samples, e.g. after the camera moved
+    pub fn clear(&mut self) {
+        self.histogram.fill([0; 3]);
+        self.samples = 0;
+    }
+
+    /// Samples `count` random points and adds the orbits of those that escape to the histogram
+    pub fn add_samples(&mut self, count: u32) {
+        let max_iterations = self.bands.iter().map(|band| band.end).max().unwrap_or(0);
+        let mut orbit = Vec::with_capacity(max_iterations as usize);
+
+        for _ in 0..count {
+            self.samples += 1;
+
+            // Uniformly distributed in the disk of radius 2, outside of which every orbit escapes immediately
+            let c = (4.0 * self.rng.next_f64() - 2.0, 4.0 * self.rng.next_f64() - 2.0);
+            if c.0 * c.0 + c.1 * c.1 > 4.0 || is_in_main_cardioid_or_period_2_bulb(c) {
+                continue;
+            }
+
+            orbit.clear();
+            let (mut x, mut y) = (0.0_f64, 0.0_f64);
+            while x * x + y * y <= 4.0 && (orbit.len() as u32) < max_iterations {
+                (x, y) = (x * x - y * y + c.0, 2.0 * x * y + c.1);
+                orbit.push((x, y));
+            }
+            if x * x + y * y <= 4.0 {
+                // Did not escape
+                continue;
+            }
+
+            let iterations = orbit.len() as u32;
+            let channels = self.bands.clone().map(|band| band.contains(&iterations));
+            if !channels.contains(&true) {
+                continue;
+            }
+
+            // The first point is `c` itself, which would only add the uniform disk of samples
+            for &(x, y) in &orbit[1..] {
+                // The set is symmetric to the real axis, so every orbit has a mirrored twin that can be counted for free
+                for position in [(x, y), (x, -y)] {
+                    if let Some(pixel) = self.pixel_index(position) {
+                        for (count, counted) in self.histogram[pixel].iter_mut().zip(channels) {
+                            if counted {
+                                *count = count.saturating_add(1);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Tone maps the histogram into tightly packed 8-bit sRGB RGBA pixels, normalizing each channel to its busiest pixel
+    pub fn render(&self) -> Vec<u8> {
+        let max_counts = [0, 1, 2].map(|channel| self.histogram.iter().map(|counts| counts[channel]).max().unwrap_or(0));
+
+        self.histogram
+            .iter()
+            .flat_map(|counts| {
+                let [r, g, b] = [0, 1, 2].map(|channel| {
+                    if max_counts[channel] == 0 {
+                        return 0;
+                    }
+                    // Encoding the density as sRGB already brings out the faint outer orbits
+                    linear_to_srgb(counts[channel] as f32 / max_counts[channel] as f32)
+                });
+                [r, g, b, 255]
+            })
+            .collect()
+    }
+
+    /// Encodes the current image as a PNG
+    pub fn write_png(&self, writer: impl Write) -> Result<()> {
+        let (width, height) = self.size;
+        let mut encoder = png::Encoder::new(writer, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+        encoder.add_text_chunk("Software".to_owned(), "mandelbrot-renderer".to_owned())?;
+        encoder.add_text_chunk("Buddhabrot.Samples".to_owned(), self.samples.to_string())?;
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.render())?;
+        writer.finish()?;
+
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_png(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_png(file)
+    }
+
+    fn pixel_index(&self, (re, im): (f64, f64)) -> Option<usize> {
+        let (width, height) = self.size;
+        let x = ((re - self.camera_center.0) / self.camera_size.0 + 1.0) / 2.0 * width as f64;
+        let y = (1.0 - (im - self.camera_center.1) / self.camera_size.1) / 2.0 * height as f64;
+
+        if x < 0.0 || y < 0.0 || x >= width as f64 || y >= height as f64 {
+            return None;
+        }
+        Some(y as usize * width as usize + x as usize)
+    }
+}
+
+/// Points in the two largest components never escape, so they can be skipped without iterating
+fn is_in_main_cardioid_or_period_2_bulb((x, y): (f64, f64)) -> bool {
+    let q = (x - 0.25) * (x - 0.25) + y * y;
+    q * (q + (x - 0.25)) <= 0.25 * y * y || (x + 1.0) * (x + 1.0) + y * y <= 0.0625
+}
+
+/// A small and fast random number generator (xorshift64*), good enough for picking samples
+#[derive(Copy, Clone, Debug)]
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        // The state must never be zero
+        Self(seed.max(1))
+    }
+
+    /// A uniformly distributed number in `0.0..1.0`
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let value = self.0.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        (value >> 11) as f64 / (1_u64 << 53) as f64
+    }
+}
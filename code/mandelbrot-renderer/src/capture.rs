@@ -0,0 +1,80 @@
+use std::io::{Read, Write};
+
+use anyhow::Result;
+
+use crate::ViewDescriptor;
+
+/// A frame that was read back from the GPU
+pub struct CapturedFrame {
+    pub width: u32,
+    pub height: u32,
+    /// Tightly packed 8-bit RGBA pixels in the sRGB color space
+    pub pixels: Vec<u8>,
+    /// The view this frame shows
+    pub view: ViewDescriptor,
+}
+
+impl CapturedFrame {
+    /// Encodes this frame as a PNG image with the view descriptor embedded as text chunks
+    pub fn write_png(&self, writer: impl Write) -> Result<()> {
+        write_png(writer, (self.width, self.height), &self.pixels, &self.view)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_png(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_png(file)
+    }
+}
+
+/// Encodes 8-bit sRGB RGBA pixels as a PNG image. Every entry of the view descriptor is stored in its own `tEXt` chunk,
+/// or an `iTXt` chunk if the value is not Latin-1, e.g. a palette name or step function with other characters.
+pub fn write_png(writer: impl Write, (width, height): (u32, u32), pixels: &[u8], view: &ViewDescriptor) -> Result<()> {
+    let mut encoder = png_encoder(writer, (width, height), view)?;
+    encoder.set_color(png::ColorType::Rgba);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Creates a PNG encoder for an 8-bit sRGB image, that already contains the view descriptor
+pub(crate) fn png_encoder<W: Write>(
+    writer: W,
+    (width, height): (u32, u32),
+    view: &ViewDescriptor,
+) -> Result<png::Encoder<'static, W>> {
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+
+    encoder.add_text_chunk("Software".to_owned(), "mandelbrot-renderer".to_owned())?;
+    for (key, value) in view.to_key_values() {
+        if value.chars().all(|c| c <= '\u{ff}') {
+            encoder.add_text_chunk(key.to_owned(), value)?;
+        } else {
+            encoder.add_itxt_chunk(key.to_owned(), value)?;
+        }
+    }
+
+    Ok(encoder)
+}
+
+/// Reads the view descriptor of a PNG image written by [`write_png`], so that the view can be restored
+pub fn read_png_view_descriptor(reader: impl Read) -> Result<ViewDescriptor> {
+    let reader = png::Decoder::new(reader).read_info()?;
+    let info = reader.info();
+
+    let mut entries: Vec<(&str, String)> = info
+        .uncompressed_latin1_text
+        .iter()
+        .map(|chunk| (chunk.keyword.as_str(), chunk.text.clone()))
+        .collect();
+    for chunk in &info.utf8_text {
+        entries.push((chunk.keyword.as_str(), chunk.get_text()?));
+    }
+
+    ViewDescriptor::from_key_values(entries.iter().map(|(key, value)| (*key, value.as_str())))
+}
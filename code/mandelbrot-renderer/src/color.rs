@@ -0,0 +1,141 @@
+/// Decodes an 8-bit sRGB value into linear light
+pub(crate) fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes linear light as an 8-bit sRGB value, like an sRGB render target does
+pub(crate) fn linear_to_srgb(value: f32) -> u8 {
+    let value = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Converts linear sRGB into OKLab (lightness, green-red, blue-yellow), see <https://bottosson.github.io/posts/oklab/>
+pub fn linear_srgb_to_oklab([r, g, b]: [f64; 3]) -> [f64; 3] {
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+/// The inverse of [`linear_srgb_to_oklab`]
+pub fn oklab_to_linear_srgb([lightness, a, b]: [f64; 3]) -> [f64; 3] {
+    let l = (lightness + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m = (lightness - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s = (lightness - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+
+    [
+        4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    ]
+}
+
+/// Converts Cartesian (lightness, a, b) coordinates into polar (lightness, chroma, hue in radians) ones
+pub fn lab_to_lch([lightness, a, b]: [f64; 3]) -> [f64; 3] {
+    [lightness, a.hypot(b), b.atan2(a)]
+}
+
+/// The inverse of [`lab_to_lch`]
+pub fn lch_to_lab([lightness, chroma, hue]: [f64; 3]) -> [f64; 3] {
+    [lightness, chroma * hue.cos(), chroma * hue.sin()]
+}
+
+// The constants of HSLuv, see <https://www.hsluv.org/>
+const LINEAR_SRGB_TO_XYZ: [[f64; 3]; 3] = [
+    [0.41239079926595, 0.35758433938387, 0.18048078840183],
+    [0.21263900587151, 0.71516867876775, 0.072192315360733],
+    [0.019330818715591, 0.11919477979462, 0.95053215224966],
+];
+const XYZ_TO_LINEAR_SRGB: [[f64; 3]; 3] = [
+    [3.240969941904521, -1.537383177570093, -0.498610760293],
+    [-0.96924363628087, 1.87596750150772, 0.041555057407175],
+    [0.055630079696993, -0.20397695888897, 1.056971514242878],
+];
+const REFERENCE_U: f64 = 0.19783000664283;
+const REFERENCE_V: f64 = 0.46831999493879;
+const KAPPA: f64 = 903.2962962;
+const EPSILON: f64 = 0.0088564516;
+
+/// Converts linear sRGB into HSLuv (lightness from 0 to 100, saturation from 0 to 100, hue in radians).
+/// The order matches [`lab_to_lch`], so that both can be interpolated the same way.
+pub fn linear_srgb_to_hsluv(color: [f64; 3]) -> [f64; 3] {
+    let [x, y, z] = LINEAR_SRGB_TO_XYZ.map(|row| row[0] * color[0] + row[1] * color[1] + row[2] * color[2]);
+
+    // CIELUV
+    let lightness = if y <= EPSILON { y * KAPPA } else { 116.0 * y.cbrt() - 16.0 };
+    let denominator = x + 15.0 * y + 3.0 * z;
+    if lightness < 1e-8 || denominator == 0.0 {
+        return [0.0; 3];
+    }
+    if lightness > 100.0 - 1e-8 {
+        return [100.0, 0.0, 0.0];
+    }
+    let u = 13.0 * lightness * (4.0 * x / denominator - REFERENCE_U);
+    let v = 13.0 * lightness * (9.0 * y / denominator - REFERENCE_V);
+
+    let [_, chroma, hue] = lab_to_lch([lightness, u, v]);
+    [lightness, chroma / max_chroma(lightness, hue) * 100.0, hue]
+}
+
+/// The inverse of [`linear_srgb_to_hsluv`]
+pub fn hsluv_to_linear_srgb([lightness, saturation, hue]: [f64; 3]) -> [f64; 3] {
+    if lightness < 1e-8 {
+        return [0.0; 3];
+    }
+    if lightness > 100.0 - 1e-8 {
+        return [1.0; 3];
+    }
+
+    let chroma = max_chroma(lightness, hue) * saturation / 100.0;
+    let [_, u, v] = lch_to_lab([lightness, chroma, hue]);
+
+    let u = u / (13.0 * lightness) + REFERENCE_U;
+    let v = v / (13.0 * lightness) + REFERENCE_V;
+    let y = if lightness <= 8.0 {
+        lightness / KAPPA
+    } else {
+        ((lightness + 16.0) / 116.0).powi(3)
+    };
+    let x = 9.0 * y * u / (4.0 * v);
+    let z = (9.0 * y - 15.0 * v * y - v * x) / (3.0 * v);
+
+    XYZ_TO_LINEAR_SRGB.map(|row| row[0] * x + row[1] * y + row[2] * z)
+}
+
+/// The largest CIELUV chroma with the given lightness and hue that is still inside the sRGB gamut
+fn max_chroma(lightness: f64, hue: f64) -> f64 {
+    let sub1 = (lightness + 16.0).powi(3) / 1560896.0;
+    let sub2 = if sub1 > EPSILON { sub1 } else { lightness / KAPPA };
+
+    let mut max_chroma = f64::INFINITY;
+    for [m1, m2, m3] in XYZ_TO_LINEAR_SRGB {
+        for t in [0.0, 1.0] {
+            // Where the channel is 0 or 1, as a line in the chroma plane
+            let top1 = (284517.0 * m1 - 94839.0 * m3) * sub2;
+            let top2 = (838422.0 * m3 + 769860.0 * m2 + 731718.0 * m1) * lightness * sub2 - 769860.0 * t * lightness;
+            let bottom = (632260.0 * m3 - 126452.0 * m2) * sub2 + 126452.0 * t;
+            let (slope, intercept) = (top1 / bottom, top2 / bottom);
+
+            let length = intercept / (hue.sin() - slope * hue.cos());
+            if length >= 0.0 {
+                max_chroma = max_chroma.min(length);
+            }
+        }
+    }
+
+    max_chroma
+}
@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+
+use crate::{Palette, Polynomial, QualityPolicy, StepFunction};
+
+/// The fractal that is rendered
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Fractal {
+    #[default]
+    Mandelbrot,
+    /// Newton's method for a [`Polynomial`], colored by the root each point converges to
+    Newton,
+    /// A user-defined iteration step, see [`StepFunction`]. Without one, this is the Mandelbrot set.
+    Custom,
+}
+
+impl Fractal {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Fractal::Mandelbrot => "mandelbrot",
+            Fractal::Newton => "newton",
+            Fractal::Custom => "custom",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "mandelbrot" => Some(Fractal::Mandelbrot),
+            "newton" => Some(Fractal::Newton),
+            "custom" => Some(Fractal::Custom),
+            _ => None,
+        }
+    }
+
+    /// The value of the `FRACTAL` shader constant
+    fn shader_value(&self) -> u32 {
+        match self {
+            Fractal::Mandelbrot => 0,
+            Fractal::Newton => 1,
+            Fractal::Custom => 2,
+        }
+    }
+}
+
+/// How points outside of the set are colored
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Coloring {
+    /// Colors from the palette, depending on the number of iterations until the orbit escaped
+    #[default]
+    EscapeTime,
+    /// Splits every iteration band into two halves, depending on the sign of the imaginary part of the escaped orbit.
+    /// The cells line up along the external rays of the set.
+    BinaryDecomposition,
+    /// Draws external rays (from the argument of the escaped orbit) and equipotential lines (from the smooth iteration count).
+    /// Equipotential lines need smooth shading.
+    FieldLines,
+}
+
+impl Coloring {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Coloring::EscapeTime => "escape-time",
+            Coloring::BinaryDecomposition => "binary-decomposition",
+            Coloring::FieldLines => "field-lines",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "escape-time" => Some(Coloring::EscapeTime),
+            "binary-decomposition" => Some(Coloring::BinaryDecomposition),
+            "field-lines" => Some(Coloring::FieldLines),
+            _ => None,
+        }
+    }
+
+    /// The value of the `COLORING` shader constant
+    fn shader_value(&self) -> u32 {
+        match self {
+            Coloring::EscapeTime => 0,
+            Coloring::BinaryDecomposition => 1,
+            Coloring::FieldLines => 2,
+        }
+    }
+}
+
+/// Options that are passed to the fragment shader as pipeline-overridable constants.
+/// Each distinct set of constants results in its own render pipeline.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ShaderConstants {
+    pub max_iterations: u32,
+    /// Use a fractional iteration count to get rid of color banding
+    pub smooth_shading: bool,
+    /// Tint the palette depending on the iteration count and position
+    pub custom_additional_colors: bool,
+    pub coloring: Coloring,
+    /// Draw the binary decomposition or field lines on top of the escape time colors instead of a white background
+    pub combine_escape_time: bool,
+}
+
+impl Default for ShaderConstants {
+    fn default() -> Self {
+        Self {
+            max_iterations: 400,
+            smooth_shading: true,
+            custom_additional_colors: true,
+            coloring: Coloring::default(),
+            combine_escape_time: true,
+        }
+    }
+}
+
+impl ShaderConstants {
+    /// Converts these constants into the form wgpu expects. Keys must match the `override` declarations in `frag.wgsl`.
+    pub(crate) fn to_pipeline_constants(self, fractal: Fractal, encode_srgb: bool) -> HashMap<String, f64> {
+        HashMap::from([
+            ("FRACTAL".to_owned(), fractal.shader_value() as f64),
+            ("ENCODE_SRGB".to_owned(), encode_srgb as u32 as f64),
+            ("MAX_ITERATIONS".to_owned(), self.max_iterations as f64),
+            ("SMOOTH_SHADING".to_owned(), self.smooth_shading as u32 as f64),
+            (
+                "CUSTOM_ADDITIONAL_COLORS".to_owned(),
+                self.custom_additional_colors as u32 as f64,
+            ),
+            ("COLORING".to_owned(), self.coloring.shader_value() as f64),
+            ("COMBINE_ESCAPE_TIME".to_owned(), self.combine_escape_time as u32 as f64),
+        ])
+    }
+}
+
+/// What to do if the surface does not support any sRGB texture format.
+/// Colors are calculated for sRGB targets and come out too dark on linear targets, unless they are converted.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum NonSrgbSurface {
+    /// Render through an sRGB view of the surface if the backend supports it, otherwise convert colors in the shader
+    #[default]
+    Convert,
+    /// Always convert colors in the shader
+    ShaderConvert,
+    /// Write colors without any conversion
+    Passthrough,
+    /// Fail to create the renderer
+    Deny,
+}
+
+/// Configuration of a [`crate::MandelbrotRenderer`], built by chaining `with_*` calls onto [`RendererConfig::new`].
+#[derive(Clone, Debug)]
+pub struct RendererConfig {
+    backends: wgpu::Backends,
+    power_preference: wgpu::PowerPreference,
+    force_fallback_adapter: bool,
+    present_mode: wgpu::PresentMode,
+    alpha_mode: Option<wgpu::CompositeAlphaMode>,
+    non_srgb_surface: NonSrgbSurface,
+    sample_count: u32,
+    desired_maximum_frame_latency: u32,
+    shader_constants: ShaderConstants,
+    fractal: Fractal,
+    polynomial: Polynomial,
+    palette: Palette,
+    step_function: Option<StepFunction>,
+    quality_policy: QualityPolicy,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        Self {
+            #[cfg(target_arch = "wasm32")]
+            backends: wgpu::Backends::GL,
+            #[cfg(not(target_arch = "wasm32"))]
+            backends: wgpu::Backends::PRIMARY,
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            present_mode: wgpu::PresentMode::AutoVsync,
+            alpha_mode: None,
+            non_srgb_surface: NonSrgbSurface::default(),
+            sample_count: 1,
+            desired_maximum_frame_latency: 2,
+            shader_constants: ShaderConstants::default(),
+            fractal: Fractal::default(),
+            polynomial: Polynomial::default(),
+            palette: Palette::default(),
+            step_function: None,
+            quality_policy: QualityPolicy::default(),
+        }
+    }
+}
+
+impl RendererConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The graphics backends wgpu may choose from. Defaults to WebGL on the web and the primary backends on native platforms.
+    pub fn with_backends(mut self, backends: wgpu::Backends) -> Self {
+        self.backends = backends;
+        self
+    }
+
+    pub fn with_power_preference(mut self, power_preference: wgpu::PowerPreference) -> Self {
+        self.power_preference = power_preference;
+        self
+    }
+
+    /// Forces wgpu to use a fallback (software) adapter, e.g. for CI machines without a GPU
+    pub fn with_force_fallback_adapter(mut self, force_fallback_adapter: bool) -> Self {
+        self.force_fallback_adapter = force_fallback_adapter;
+        self
+    }
+
+    /// Falls back to [`wgpu::PresentMode::Fifo`] if the requested mode is not supported by the surface
+    pub fn with_present_mode(mut self, present_mode: wgpu::PresentMode) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+
+    /// Shorthand for choosing between [`wgpu::PresentMode::AutoVsync`] and [`wgpu::PresentMode::AutoNoVsync`]
+    pub fn with_vsync(self, vsync: bool) -> Self {
+        self.with_present_mode(if vsync {
+            wgpu::PresentMode::AutoVsync
+        } else {
+            wgpu::PresentMode::AutoNoVsync
+        })
+    }
+
+    /// Falls back to the first supported alpha mode if the requested mode is not supported or none is set
+    pub fn with_alpha_mode(mut self, alpha_mode: wgpu::CompositeAlphaMode) -> Self {
+        self.alpha_mode = Some(alpha_mode);
+        self
+    }
+
+    pub fn with_non_srgb_surface(mut self, non_srgb_surface: NonSrgbSurface) -> Self {
+        self.non_srgb_surface = non_srgb_surface;
+        self
+    }
+
+    /// The number of samples per pixel used for multisample anti-aliasing. `1` disables MSAA.
+    pub fn with_sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    pub fn with_desired_maximum_frame_latency(mut self, desired_maximum_frame_latency: u32) -> Self {
+        self.desired_maximum_frame_latency = desired_maximum_frame_latency;
+        self
+    }
+
+    pub fn with_shader_constants(mut self, shader_constants: ShaderConstants) -> Self {
+        self.shader_constants = shader_constants;
+        self
+    }
+
+    pub fn with_fractal(mut self, fractal: Fractal) -> Self {
+        self.fractal = fractal;
+        self
+    }
+
+    /// The polynomial of [`Fractal::Newton`]
+    pub fn with_polynomial(mut self, polynomial: Polynomial) -> Self {
+        self.polynomial = polynomial;
+        self
+    }
+
+    pub fn with_palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    /// The iteration step of [`Fractal::Custom`]
+    pub fn with_step_function(mut self, step_function: StepFunction) -> Self {
+        self.step_function = Some(step_function);
+        self
+    }
+
+    /// When to render frames at a lower resolution. Defaults to always rendering at full resolution.
+    pub fn with_quality_policy(mut self, quality_policy: QualityPolicy) -> Self {
+        self.quality_policy = quality_policy;
+        self
+    }
+
+    pub fn backends(&self) -> wgpu::Backends {
+        self.backends
+    }
+
+    pub fn power_preference(&self) -> wgpu::PowerPreference {
+        self.power_preference
+    }
+
+    pub fn force_fallback_adapter(&self) -> bool {
+        self.force_fallback_adapter
+    }
+
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.present_mode
+    }
+
+    pub fn alpha_mode(&self) -> Option<wgpu::CompositeAlphaMode> {
+        self.alpha_mode
+    }
+
+    pub fn non_srgb_surface(&self) -> NonSrgbSurface {
+        self.non_srgb_surface
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    pub fn desired_maximum_frame_latency(&self) -> u32 {
+        self.desired_maximum_frame_latency
+    }
+
+    pub fn shader_constants(&self) -> ShaderConstants {
+        self.shader_constants
+    }
+
+    pub fn fractal(&self) -> Fractal {
+        self.fractal
+    }
+
+    pub fn polynomial(&self) -> Polynomial {
+        self.polynomial
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    pub fn step_function(&self) -> Option<&StepFunction> {
+        self.step_function.as_ref()
+    }
+
+    pub fn quality_policy(&self) -> QualityPolicy {
+        self.quality_policy
+    }
+}
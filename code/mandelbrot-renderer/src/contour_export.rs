@@ -0,0 +1,310 @@
+//! Vector artwork of iteration bands, traced with marching squares.
+//!
+//! For every level, the region where the smooth iteration count is at least that level is traced into closed SVG paths
+//! and filled with the palette color of the level. Levels are drawn from the lowest to the highest, so every band shows
+//! the color of the level it starts at, and the set itself is drawn in black on top. The palette tint of
+//! [`crate::ShaderConstants::custom_additional_colors`] depends on the position and is left out.
+
+use std::{collections::HashMap, fmt::Write as _, io::Write};
+
+use anyhow::{bail, Result};
+
+use crate::{color::linear_to_srgb, IterationData, Palette};
+
+/// Writes the contours of an [`IterationData`] as SVG, see the [module documentation](self)
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContourExport {
+    levels: Vec<f32>,
+    stroke_width: f32,
+    palette: Palette,
+}
+
+impl Default for ContourExport {
+    fn default() -> Self {
+        Self {
+            levels: (1..=32).map(|level| level as f32).collect(),
+            stroke_width: 0.0,
+            palette: Palette::default(),
+        }
+    }
+}
+
+impl ContourExport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The iteration counts to trace. Defaults to every whole iteration from 1 to 32.
+    pub fn with_levels(mut self, levels: Vec<f32>) -> Self {
+        self.levels = levels;
+        self
+    }
+
+    /// Outlines every contour in black, e.g. for pen plotters. `0` disables outlines.
+    pub fn with_stroke_width(mut self, stroke_width: f32) -> Self {
+        self.stroke_width = stroke_width;
+        self
+    }
+
+    /// Should be the palette the data was captured with, as the view descriptor only has its name
+    pub fn with_palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    pub fn levels(&self) -> &[f32] {
+        &self.levels
+    }
+
+    pub fn stroke_width(&self) -> f32 {
+        self.stroke_width
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// Writes an SVG image with one unit per pixel of the data
+    pub fn write_svg(&self, data: &IterationData, mut writer: impl Write) -> Result<()> {
+        if self.levels.iter().any(|level| !level.is_finite()) {
+            bail!("Contour levels have to be finite");
+        }
+        if data.width == 0 || data.height == 0 {
+            bail!("Cannot trace contours of an empty image");
+        }
+        let mut levels = self.levels.clone();
+        levels.sort_by(f32::total_cmp);
+
+        let (width, height) = (data.width, data.height);
+        writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            writer,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+        )?;
+        writeln!(writer, "<metadata>")?;
+        for (key, value) in data.view.to_key_values() {
+            writeln!(writer, "{}: {}", key, xml_escape(&value))?;
+        }
+        writeln!(writer, "</metadata>")?;
+
+        let stroke = if self.stroke_width > 0.0 {
+            format!(r#" stroke="black" stroke-width="{}" stroke-linejoin="round""#, self.stroke_width)
+        } else {
+            String::new()
+        };
+
+        // Everything below the lowest level
+        let background = levels.first().map_or(0.0, |level| level - 1.0);
+        writeln!(
+            writer,
+            r#"<rect width="{width}" height="{height}" fill="{}"/>"#,
+            hex_color(self.palette.color(background.max(0.0) as f64))
+        )?;
+
+        let field = Field::new(data);
+        let max_iterations = data.view.shader_constants.max_iterations as f32;
+        let fills = levels
+            .iter()
+            .filter(|level| **level < max_iterations)
+            .map(|level| (*level, hex_color(self.palette.color(*level as f64))))
+            .chain([(max_iterations, "#000000".to_owned())]);
+        for (level, fill) in fills {
+            let path = field.contour_path(level);
+            if !path.is_empty() {
+                writeln!(writer, r#"<path fill="{fill}" fill-rule="evenodd"{stroke} d="{path}"/>"#)?;
+            }
+        }
+
+        writeln!(writer, "</svg>")?;
+
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_svg(&self, data: &IterationData, path: impl AsRef<std::path::Path>) -> Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_svg(data, file)
+    }
+}
+
+/// A grid edge, identified by its top or left vertex
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct GridEdge {
+    x: usize,
+    y: usize,
+    horizontal: bool,
+}
+
+/// The iteration counts, surrounded by a copy of the outermost pixels and a border of `-inf` so that every contour is
+/// closed. Both lie on the edges of the image, all other vertices in the centers of their pixels.
+struct Field {
+    width: usize,
+    height: usize,
+    values: Vec<f32>,
+    image_size: (f32, f32),
+}
+
+impl Field {
+    fn new(data: &IterationData) -> Self {
+        let (width, height) = (data.width as usize + 4, data.height as usize + 4);
+        let mut values = vec![f32::NEG_INFINITY; width * height];
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                let pixel_x = x.saturating_sub(2).min(data.width as usize - 1);
+                let pixel_y = y.saturating_sub(2).min(data.height as usize - 1);
+                values[y * width + x] = data.iterations[pixel_y * data.width as usize + pixel_x];
+            }
+        }
+
+        Self {
+            width,
+            height,
+            values,
+            image_size: (data.width as f32, data.height as f32),
+        }
+    }
+
+    fn value(&self, x: usize, y: usize) -> f32 {
+        self.values[y * self.width + x]
+    }
+
+    fn position(&self, x: usize, y: usize) -> (f32, f32) {
+        (
+            (x as f32 - 1.5).clamp(0.0, self.image_size.0),
+            (y as f32 - 1.5).clamp(0.0, self.image_size.1),
+        )
+    }
+
+    /// Where the contour crosses an edge with one vertex inside and one outside
+    fn crossing(&self, edge: GridEdge, level: f32) -> (f32, f32) {
+        let (x1, y1) = if edge.horizontal { (edge.x + 1, edge.y) } else { (edge.x, edge.y + 1) };
+        let (value0, value1) = (self.value(edge.x, edge.y), self.value(x1, y1));
+        let (position0, position1) = (self.position(edge.x, edge.y), self.position(x1, y1));
+
+        // Both vertices lie on the edge of the image
+        if value0 == f32::NEG_INFINITY || value1 == f32::NEG_INFINITY {
+            return position0;
+        }
+
+        let t = (level - value0) / (value1 - value0);
+        (
+            position0.0 + t * (position1.0 - position0.0),
+            position0.1 + t * (position1.1 - position0.1),
+        )
+    }
+
+    /// The segments of the contour in every cell, as pairs of the edges they connect
+    fn segments(&self, level: f32) -> Vec<(GridEdge, GridEdge)> {
+        let mut segments = Vec::new();
+
+        for y in 0..self.height - 1 {
+            for x in 0..self.width - 1 {
+                let corners = [(x, y), (x + 1, y), (x + 1, y + 1), (x, y + 1)].map(|(x, y)| self.value(x, y));
+                let [top_left, top_right, bottom_right, bottom_left] = corners.map(|value| value >= level);
+                let case = (top_left as u8) << 3 | (top_right as u8) << 2 | (bottom_right as u8) << 1 | bottom_left as u8;
+
+                let top = GridEdge { x, y, horizontal: true };
+                let bottom = GridEdge { x, y: y + 1, horizontal: true };
+                let left = GridEdge { x, y, horizontal: false };
+                let right = GridEdge { x: x + 1, y, horizontal: false };
+                // Saddles are resolved by the average of the corners
+                let center_inside = corners.iter().sum::<f32>() / 4.0 >= level;
+
+                match case {
+                    1 | 14 => segments.push((left, bottom)),
+                    2 | 13 => segments.push((bottom, right)),
+                    3 | 12 => segments.push((left, right)),
+                    4 | 11 => segments.push((top, right)),
+                    6 | 9 => segments.push((top, bottom)),
+                    7 | 8 => segments.push((top, left)),
+                    5 if center_inside => segments.extend([(top, left), (right, bottom)]),
+                    5 => segments.extend([(top, right), (left, bottom)]),
+                    10 if center_inside => segments.extend([(top, right), (left, bottom)]),
+                    10 => segments.extend([(top, left), (right, bottom)]),
+                    _ => {}
+                }
+            }
+        }
+
+        segments
+    }
+
+    /// Joins the segments into closed loops and formats them as SVG path data
+    fn contour_path(&self, level: f32) -> String {
+        let segments = self.segments(level);
+
+        // Every crossed edge is shared by exactly two segments of neighbouring cells
+        let mut segments_at: HashMap<GridEdge, Vec<usize>> = HashMap::new();
+        for (i, (start, end)) in segments.iter().enumerate() {
+            segments_at.entry(*start).or_default().push(i);
+            segments_at.entry(*end).or_default().push(i);
+        }
+
+        let mut visited = vec![false; segments.len()];
+        let mut path = String::new();
+        for first in 0..segments.len() {
+            if visited[first] {
+                continue;
+            }
+
+            let (start, mut edge) = segments[first];
+            let mut segment = first;
+            let mut points = vec![self.crossing(start, level)];
+            loop {
+                visited[segment] = true;
+                if edge == start {
+                    break;
+                }
+                points.push(self.crossing(edge, level));
+
+                let Some(&next) = segments_at[&edge].iter().find(|next| !visited[**next]) else {
+                    break;
+                };
+                let (next_start, next_end) = segments[next];
+                edge = if next_start == edge { next_end } else { next_start };
+                segment = next;
+            }
+
+            write_loop(&mut path, &points);
+        }
+
+        path
+    }
+}
+
+/// Appends a closed loop to SVG path data, leaving out points on straight lines, e.g. along the edges of the image
+fn write_loop(path: &mut String, points: &[(f32, f32)]) {
+    // Loops along the edges of the image pass through the corners several times
+    let mut points = points.to_vec();
+    points.dedup();
+    while points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+
+    let mut command = 'M';
+    for (i, point) in points.iter().enumerate() {
+        let previous = points[(i + points.len() - 1) % points.len()];
+        let next = points[(i + 1) % points.len()];
+        let cross = (point.0 - previous.0) * (next.1 - previous.1) - (point.1 - previous.1) * (next.0 - previous.0);
+        if cross.abs() < 1e-6 && points.len() > 3 && i > 0 {
+            continue;
+        }
+
+        let _ = write!(path, "{command}{:.2} {:.2}", point.0, point.1);
+        command = 'L';
+    }
+    path.push('Z');
+}
+
+/// Formats a linear color as an sRGB hex color
+fn hex_color(color: [f64; 3]) -> String {
+    let [r, g, b] = color.map(|channel| linear_to_srgb(channel as f32));
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,278 @@
+use crate::{
+    color::linear_to_srgb, Coloring, Fractal, IterationData, Palette, Polynomial, ShaderConstants, ViewDescriptor,
+    Viewport,
+};
+
+/// The escape-time result for a single point, see `Escape` in `frag.wgsl`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Escape {
+    /// The (fractional) number of iterations until the orbit escaped. Equals `max_iterations` inside the set.
+    pub iterations: f64,
+    /// The estimated distance to the set on the complex plane, `0` inside the set
+    pub distance: f64,
+    /// The value of `z` once the orbit escaped, `0` inside the set
+    pub z: (f64, f64),
+}
+
+/// Renders the same images as the fragment shader on the CPU, for machines without a GPU.
+/// Calculations use `f64`, so this renderer can zoom in a lot further than the GPU.
+#[derive(Clone, Debug, Default)]
+pub struct CpuRenderer {
+    shader_constants: ShaderConstants,
+    fractal: Fractal,
+    polynomial: Polynomial,
+    palette: Palette,
+}
+
+impl CpuRenderer {
+    pub fn new(shader_constants: ShaderConstants) -> Self {
+        Self {
+            shader_constants,
+            fractal: Fractal::default(),
+            polynomial: Polynomial::default(),
+            palette: Palette::default(),
+        }
+    }
+
+    /// Step functions of [`Fractal::Custom`] only run on the GPU, the CPU renders the Mandelbrot set instead
+    pub fn with_fractal(mut self, fractal: Fractal) -> Self {
+        self.fractal = fractal;
+        self
+    }
+
+    /// The polynomial of [`Fractal::Newton`]
+    pub fn with_polynomial(mut self, polynomial: Polynomial) -> Self {
+        self.polynomial = polynomial;
+        self
+    }
+
+    pub fn with_palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    pub fn shader_constants(&self) -> ShaderConstants {
+        self.shader_constants
+    }
+
+    pub fn fractal(&self) -> Fractal {
+        self.fractal
+    }
+
+    pub fn polynomial(&self) -> Polynomial {
+        self.polynomial
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// Renders an image into tightly packed 8-bit sRGB RGBA pixels.
+    /// The camera works like the one of [`crate::MandelbrotRenderer::render`].
+    pub fn render(&self, camera_center: (f64, f64), camera_size: (f64, f64), (width, height): (u32, u32)) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+
+        for y in 0..height {
+            // Sample at pixel centers, like the rasterizer does
+            let im = camera_center.1 + (1.0 - 2.0 * (y as f64 + 0.5) / height as f64) * camera_size.1;
+            for x in 0..width {
+                let re = camera_center.0 + (2.0 * (x as f64 + 0.5) / width as f64 - 1.0) * camera_size.0;
+                pixels.extend_from_slice(&self.color((re, im)));
+            }
+        }
+
+        pixels
+    }
+
+    /// Calculates the same data as [`crate::MandelbrotRenderer::capture_iteration_data`]
+    pub fn iteration_data(&self, camera_center: (f64, f64), camera_size: (f64, f64), (width, height): (u32, u32)) -> IterationData {
+        let mut iterations = Vec::with_capacity(width as usize * height as usize);
+        let mut distances = Vec::with_capacity(width as usize * height as usize);
+
+        for y in 0..height {
+            let im = camera_center.1 + (1.0 - 2.0 * (y as f64 + 0.5) / height as f64) * camera_size.1;
+            for x in 0..width {
+                let re = camera_center.0 + (2.0 * (x as f64 + 0.5) / width as f64 - 1.0) * camera_size.0;
+                let escape = self.fractal_escape((re, im));
+                iterations.push(escape.iterations as f32);
+                distances.push(escape.distance as f32);
+            }
+        }
+
+        IterationData {
+            width,
+            height,
+            iterations,
+            distances,
+            view: ViewDescriptor {
+                viewport: Viewport::new(
+                    (camera_center.0 as f32, camera_center.1 as f32),
+                    -camera_size.0.log10() as f32,
+                ),
+                fractal: self.fractal,
+                polynomial: self.polynomial,
+                step_function: None,
+                palette: self.palette.name().to_owned(),
+                palette_interpolation: self.palette.interpolation(),
+                shader_constants: self.shader_constants,
+            },
+        }
+    }
+
+    /// Calculates the 8-bit sRGB color of a point on the complex plane
+    pub fn color(&self, position: (f64, f64)) -> [u8; 4] {
+        if self.fractal == Fractal::Newton {
+            return self.newton_color(position);
+        }
+
+        let max_iterations = self.shader_constants.max_iterations;
+        let escape = self.escape(position);
+        let iterations = escape.iterations;
+        let whole_iterations = iterations.floor() as u32;
+
+        if whole_iterations == max_iterations {
+            return [0, 0, 0, 255];
+        }
+
+        let mut color = self.palette.color(iterations);
+
+        if self.shader_constants.custom_additional_colors {
+            let relative_iterations = iterations / max_iterations as f64;
+            let tint = 0.3 * position.1.sin();
+            color[0] += 0.4 * relative_iterations + tint;
+            color[1] += 0.1 * relative_iterations;
+            color[2] += 0.6 - relative_iterations + tint;
+        }
+
+        if self.shader_constants.coloring != Coloring::EscapeTime {
+            color = self.structure_color(color, escape);
+        }
+
+        let [r, g, b] = color.map(|channel| linear_to_srgb(channel as f32));
+        [r, g, b, 255]
+    }
+
+    /// Like `newton_color` in `frag.wgsl`
+    fn newton_color(&self, position: (f64, f64)) -> [u8; 4] {
+        let Some(convergence) = self.polynomial.converge(position, self.shader_constants.max_iterations) else {
+            return [0, 0, 0, 255];
+        };
+
+        let degree = self.polynomial.degree();
+        let length = self.palette.colors().len();
+        let root_color = self
+            .palette
+            .entry((NEWTON_PALETTE_OFFSET * length as f64) as usize + convergence.root * length / degree);
+        let brightness = NEWTON_SHADING.powf(convergence.iterations);
+
+        let [r, g, b] = root_color.map(|channel| linear_to_srgb((channel as f64 * brightness) as f32));
+        [r, g, b, 255]
+    }
+
+    /// Like `structure_color` in `frag.wgsl`
+    fn structure_color(&self, escape_time_color: [f64; 3], escape: Escape) -> [f64; 3] {
+        let (color, dark) = if self.shader_constants.combine_escape_time {
+            (escape_time_color, escape_time_color.map(|channel| channel * 0.25))
+        } else {
+            ([1.0; 3], [0.0; 3])
+        };
+
+        let is_dark = match self.shader_constants.coloring {
+            Coloring::EscapeTime => false,
+            Coloring::BinaryDecomposition => escape.z.1 < 0.0,
+            Coloring::FieldLines => {
+                let angle = escape.z.1.atan2(escape.z.0) / std::f64::consts::TAU + 0.5;
+                let mut line_distance = ((angle * 2.0 + 0.5).fract() - 0.5).abs();
+                if self.shader_constants.smooth_shading {
+                    line_distance = line_distance.min(((escape.iterations + 0.5).fract() - 0.5).abs());
+                }
+                line_distance < FIELD_LINE_WIDTH
+            }
+        };
+
+        if is_dark {
+            dark
+        } else {
+            color
+        }
+    }
+
+    /// Iterates a point of the current fractal, like `iteration_data` in `frag.wgsl`.
+    /// Newton fractals only have the number of iterations until the point converged.
+    pub fn fractal_escape(&self, position: (f64, f64)) -> Escape {
+        match self.fractal {
+            Fractal::Newton => Escape {
+                iterations: self
+                    .polynomial
+                    .converge(position, self.shader_constants.max_iterations)
+                    .map_or(self.shader_constants.max_iterations as f64, |convergence| convergence.iterations),
+                distance: 0.0,
+                z: (0.0, 0.0),
+            },
+            Fractal::Mandelbrot | Fractal::Custom => self.escape(position),
+        }
+    }
+
+    /// Iterates a point of the complex plane, exactly like `mandelbrot` in `frag.wgsl`
+    pub fn escape(&self, (x0, y0): (f64, f64)) -> Escape {
+        let max_iterations = self.shader_constants.max_iterations;
+        let inside = Escape {
+            iterations: max_iterations as f64,
+            distance: 0.0,
+            z: (0.0, 0.0),
+        };
+
+        // Main cardioid check
+        let q = (x0 - 0.25) * (x0 - 0.25) + y0 * y0;
+        if q * (q + (x0 - 0.25)) <= 0.25 * y0 * y0 {
+            return inside;
+        }
+
+        let escape_radius_squared = if self.shader_constants.smooth_shading { 1000.0 } else { 4.0 };
+
+        let (mut x, mut y, mut x2, mut y2) = (0.0, 0.0, 0.0, 0.0);
+        // Derivative of z with respect to c, for the distance estimate
+        let (mut dx, mut dy) = (0.0, 0.0);
+        let mut i = 0;
+        while x2 + y2 <= escape_radius_squared && i < max_iterations {
+            // dz = 2 * z * dz + 1
+            (dx, dy) = (2.0 * (x * dx - y * dy) + 1.0, 2.0 * (x * dy + y * dx));
+
+            y = (x + x) * y + y0;
+            x = x2 - y2 + x0;
+            x2 = x * x;
+            y2 = y * y;
+            i += 1;
+        }
+
+        if i == max_iterations {
+            return inside;
+        }
+
+        // |z| * ln|z| / |dz|
+        let abs_z = (x2 + y2).sqrt();
+        let distance = abs_z * abs_z.ln() / dx.hypot(dy);
+
+        let iterations = if self.shader_constants.smooth_shading {
+            let log_zn = (x2 + y2).ln() / 2.0;
+            let nu = (log_zn / 2.0_f64.ln()).log2();
+            i as f64 + 1.0 - nu
+        } else {
+            i as f64
+        };
+
+        Escape {
+            iterations,
+            distance,
+            z: (x, y),
+        }
+    }
+}
+
+/// The width of field lines, as a fraction of an iteration band
+const FIELD_LINE_WIDTH: f64 = 0.04;
+
+/// Where in the palette the color of the first root of a Newton fractal is. The other roots are spread out evenly from there.
+const NEWTON_PALETTE_OFFSET: f64 = 0.375;
+/// How much darker a Newton fractal gets with every step it takes to converge
+const NEWTON_SHADING: f64 = 0.9;
@@ -0,0 +1,247 @@
+//! Heightfield meshes of escape-time data, for 3D printing and 3D scenes.
+//!
+//! Every sample of an [`IterationData`] becomes a vertex, so the resolution of the mesh is the one the data was captured
+//! with. The mesh lies in the xy-plane with z pointing up, and `x` to the right and `y` to the top of the image, as seen
+//! from above. With a base plate, the surface is closed off by walls and a bottom into a watertight solid.
+
+use std::io::Write;
+
+use anyhow::{bail, Result};
+
+use crate::{IterationData, ViewDescriptor};
+
+/// Which channel of the escape-time data the height is taken from
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum HeightSource {
+    /// Rises with the logarithm of the smooth iteration count, the set itself is a plateau at the top
+    #[default]
+    SmoothIterations,
+    /// Rises towards the set, which results in sharp ridges along filaments that iteration counts hardly show
+    DistanceEstimate,
+}
+
+/// Converts escape-time data into a [`Mesh`], see the [module documentation](self)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HeightfieldExport {
+    height_source: HeightSource,
+    width: f32,
+    height_scale: f32,
+    base_thickness: f32,
+}
+
+impl Default for HeightfieldExport {
+    fn default() -> Self {
+        Self {
+            height_source: HeightSource::default(),
+            width: 100.0,
+            height_scale: 10.0,
+            base_thickness: 2.0,
+        }
+    }
+}
+
+impl HeightfieldExport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_height_source(mut self, height_source: HeightSource) -> Self {
+        self.height_source = height_source;
+        self
+    }
+
+    /// The width of the mesh in model units (usually millimeters for 3D printing), the depth follows from the aspect ratio
+    pub fn with_width(mut self, width: f32) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// The height difference between the lowest and highest possible point of the surface, in model units
+    pub fn with_height_scale(mut self, height_scale: f32) -> Self {
+        self.height_scale = height_scale;
+        self
+    }
+
+    /// The thickness of the base plate below the lowest point of the surface. `0` exports just the surface, without a base plate.
+    pub fn with_base_thickness(mut self, base_thickness: f32) -> Self {
+        self.base_thickness = base_thickness;
+        self
+    }
+
+    pub fn height_source(&self) -> HeightSource {
+        self.height_source
+    }
+
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    pub fn height_scale(&self) -> f32 {
+        self.height_scale
+    }
+
+    pub fn base_thickness(&self) -> f32 {
+        self.base_thickness
+    }
+
+    pub fn mesh(&self, data: &IterationData) -> Result<Mesh> {
+        let (width, height) = (data.width as usize, data.height as usize);
+        if width < 2 || height < 2 {
+            bail!("A heightfield needs at least 2x2 samples, not {width}x{height}");
+        }
+
+        let spacing = self.width / (width - 1) as f32;
+        let mut vertices = Vec::with_capacity(width * height + 2 * (width + height) + 1);
+        let mut triangles = Vec::with_capacity(2 * (width - 1) * (height - 1) + 6 * (width + height));
+
+        // Vertex `y * width + x` is sample `x` of row `height - 1 - y`, as rows start at the top of the image
+        for y in 0..height {
+            let row = height - 1 - y;
+            for x in 0..width {
+                let sample = row * width + x;
+                let z = self.base_thickness + self.height_scale * self.relative_height(data, sample);
+                vertices.push([x as f32 * spacing, y as f32 * spacing, z]);
+            }
+        }
+
+        let index = |x: usize, y: usize| (y * width + x) as u32;
+        for y in 0..height - 1 {
+            for x in 0..width - 1 {
+                // Counter-clockwise as seen from above, so that the normals point up
+                triangles.push([index(x, y), index(x + 1, y), index(x + 1, y + 1)]);
+                triangles.push([index(x, y), index(x + 1, y + 1), index(x, y + 1)]);
+            }
+        }
+
+        if self.base_thickness > 0.0 {
+            // The outline of the surface, counter-clockwise as seen from above
+            let outline: Vec<u32> = (0..width - 1)
+                .map(|x| index(x, 0))
+                .chain((0..height - 1).map(|y| index(width - 1, y)))
+                .chain((1..width).rev().map(|x| index(x, height - 1)))
+                .chain((1..height).rev().map(|y| index(0, y)))
+                .collect();
+
+            // A copy of the outline on the ground, and the center of the bottom to fan out from
+            let bottom_start = vertices.len() as u32;
+            for &top in &outline {
+                let [x, y, _] = vertices[top as usize];
+                vertices.push([x, y, 0.0]);
+            }
+            let center = vertices.len() as u32;
+            vertices.push([self.width / 2.0, (height - 1) as f32 * spacing / 2.0, 0.0]);
+
+            for i in 0..outline.len() {
+                let next = (i + 1) % outline.len();
+                let (top, next_top) = (outline[i], outline[next]);
+                let (bottom, next_bottom) = (bottom_start + i as u32, bottom_start + next as u32);
+
+                triangles.push([top, bottom, next_bottom]);
+                triangles.push([top, next_bottom, next_top]);
+                triangles.push([center, next_bottom, bottom]);
+            }
+        }
+
+        Ok(Mesh {
+            vertices,
+            triangles,
+            view: data.view.clone(),
+        })
+    }
+
+    /// The height of a sample from `0` to `1`
+    fn relative_height(&self, data: &IterationData, sample: usize) -> f32 {
+        match self.height_source {
+            HeightSource::SmoothIterations => {
+                let max_iterations = data.view.shader_constants.max_iterations as f32;
+                (data.iterations[sample].max(0.0).ln_1p() / max_iterations.ln_1p()).clamp(0.0, 1.0)
+            }
+            HeightSource::DistanceEstimate => {
+                // Falls off logarithmically, from the set to one image width away from it
+                let distance_in_pixels = (data.distances[sample].max(0.0) as f64 / data.pixel_size()) as f32;
+                1.0 - (distance_in_pixels.ln_1p() / (data.width as f32).ln_1p()).min(1.0)
+            }
+        }
+    }
+}
+
+/// A triangle mesh with counter-clockwise winding, see [`HeightfieldExport`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<[f32; 3]>,
+    pub triangles: Vec<[u32; 3]>,
+    /// The view the mesh was created from
+    pub view: ViewDescriptor,
+}
+
+impl Mesh {
+    /// Writes a binary STL file. STL has no room for metadata, so the view descriptor is lost.
+    pub fn write_stl(&self, mut writer: impl Write) -> Result<()> {
+        let mut header = [0; 80];
+        let description = b"mandelbrot-renderer heightfield";
+        header[..description.len()].copy_from_slice(description);
+        writer.write_all(&header)?;
+        writer.write_all(&(self.triangles.len() as u32).to_le_bytes())?;
+
+        for triangle in &self.triangles {
+            let [a, b, c] = triangle.map(|vertex| self.vertices[vertex as usize]);
+            let normal = normal(a, b, c);
+            for value in [normal, a, b, c].iter().flatten() {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            // Attribute byte count, unused
+            writer.write_all(&[0; 2])?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a Wavefront OBJ file, with the view descriptor in comments at the top
+    pub fn write_obj(&self, mut writer: impl Write) -> Result<()> {
+        writeln!(writer, "# mandelbrot-renderer heightfield")?;
+        for (key, value) in self.view.to_key_values() {
+            // Step functions can span several lines
+            writeln!(writer, "# {key}: {}", value.replace('\n', "\\n"))?;
+        }
+
+        for [x, y, z] in &self.vertices {
+            writeln!(writer, "v {x} {y} {z}")?;
+        }
+        for [a, b, c] in &self.triangles {
+            // Indices start at 1
+            writeln!(writer, "f {} {} {}", a + 1, b + 1, c + 1)?;
+        }
+
+        Ok(())
+    }
+
+    /// Saves the mesh as OBJ if the path ends in `.obj`, and as binary STL otherwise
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        let path = path.as_ref();
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+        if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("obj")) {
+            self.write_obj(file)
+        } else {
+            self.write_stl(file)
+        }
+    }
+}
+
+/// The unit normal of a counter-clockwise triangle, or zero for degenerate ones
+fn normal(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> [f32; 3] {
+    let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+    let cross = [
+        u[1] * v[2] - u[2] * v[1],
+        u[2] * v[0] - u[0] * v[2],
+        u[0] * v[1] - u[1] * v[0],
+    ];
+
+    let length = (cross[0] * cross[0] + cross[1] * cross[1] + cross[2] * cross[2]).sqrt();
+    if length == 0.0 {
+        return [0.0; 3];
+    }
+    cross.map(|component| component / length)
+}
@@ -0,0 +1,139 @@
+//! A heads-up display with the position of the camera, the point under the pointer and the frame rate.
+//!
+//! This module only produces the text. The `Mandelbrot` component of the game shows it in an HTML overlay, and the native
+//! example rasterizes it into an [`Overlay`](crate::Overlay) that is drawn over the fractal.
+
+use web_time::{Duration, Instant};
+
+use crate::{CpuRenderer, Fractal, Viewport};
+
+/// Coordinates never get more decimal places than `f64` can tell apart
+const MAX_COORDINATE_DECIMALS: usize = 17;
+
+/// What the HUD shows, see [`Hud::lines`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hud {
+    viewport: Viewport,
+    width: u32,
+    pointer: Option<(f64, f64)>,
+    iterations: Option<(f64, u32)>,
+    fps: Option<f32>,
+}
+
+impl Hud {
+    /// `width` is the width of the view in pixels, which decides how many decimal places coordinates get
+    pub fn new(viewport: Viewport, width: u32) -> Self {
+        Self {
+            viewport,
+            width,
+            pointer: None,
+            iterations: None,
+            fps: None,
+        }
+    }
+
+    /// The point on the complex plane under the pointer, with the iterations `renderer` calculates for it.
+    /// Custom step functions only run on the GPU, so their iterations are left out.
+    pub fn with_pointer(mut self, pointer: (f64, f64), renderer: &CpuRenderer) -> Self {
+        self.pointer = Some(pointer);
+        self.iterations = (renderer.fractal() != Fractal::Custom).then(|| {
+            let max_iterations = renderer.shader_constants().max_iterations;
+            (renderer.fractal_escape(pointer).iterations, max_iterations)
+        });
+        self
+    }
+
+    pub fn with_fps(mut self, fps: f32) -> Self {
+        self.fps = Some(fps);
+        self
+    }
+
+    /// One line per value, e.g. `Center: -0.7453 + 0.1127i`
+    pub fn lines(&self) -> Vec<String> {
+        let decimals = coordinate_decimals(self.viewport, self.width);
+        let center = (self.viewport.center.0 as f64, self.viewport.center.1 as f64);
+
+        let mut lines = vec![
+            format!("Center: {}", format_complex(center, decimals)),
+            format!("Zoom: {}", format_zoom(self.viewport.zoom_exponent)),
+        ];
+        if let Some(pointer) = self.pointer {
+            lines.push(format!("Pointer: {}", format_complex(pointer, decimals)));
+        }
+        if let Some((iterations, max_iterations)) = self.iterations {
+            if iterations >= max_iterations as f64 {
+                lines.push(format!("Iterations: {max_iterations}+"));
+            } else {
+                lines.push(format!("Iterations: {iterations:.1}"));
+            }
+        }
+        if let Some(fps) = self.fps {
+            lines.push(format!("FPS: {fps:.1}"));
+        }
+
+        lines
+    }
+}
+
+/// The number of decimal places that tell neighbouring pixels of a view `width` pixels wide apart
+pub fn coordinate_decimals(viewport: Viewport, width: u32) -> usize {
+    // The horizontal camera size is half the width of the view
+    let pixel_size = 2.0 * 10.0_f64.powf(-viewport.zoom_exponent as f64) / width.max(1) as f64;
+
+    (-pixel_size.log10()).ceil().clamp(1.0, MAX_COORDINATE_DECIMALS as f64) as usize
+}
+
+/// Formats a point on the complex plane like `-0.75 + 0.1i`
+pub fn format_complex((re, im): (f64, f64), decimals: usize) -> String {
+    let sign = if im.is_sign_negative() { '-' } else { '+' };
+
+    format!("{re:.decimals$} {sign} {:.decimals$}i", im.abs())
+}
+
+/// Formats the magnification `10^zoom_exponent`, in scientific notation once it gets large
+pub fn format_zoom(zoom_exponent: f32) -> String {
+    let zoom = 10.0_f64.powf(zoom_exponent as f64);
+    if zoom < 1000.0 {
+        format!("{zoom:.2}x")
+    } else {
+        format!("{zoom:.2e}x")
+    }
+}
+
+/// Counts frames, and calculates the average frame rate every second
+#[derive(Debug)]
+pub struct FpsCounter {
+    current_num_frames: usize,
+    last_calculation: Instant,
+}
+
+impl Default for FpsCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FpsCounter {
+    pub fn new() -> Self {
+        Self {
+            current_num_frames: 0,
+            last_calculation: Instant::now(),
+        }
+    }
+
+    /// Returns the newly calculated frame rate, once a second has passed since the last one
+    pub fn record_frame(&mut self) -> Option<f32> {
+        self.current_num_frames += 1;
+
+        let now = Instant::now();
+        let dt = now.duration_since(self.last_calculation);
+
+        (dt > Duration::from_secs(1)).then(|| {
+            let fps = self.current_num_frames as f32 / dt.as_secs_f32();
+            self.current_num_frames = 0;
+            self.last_calculation = now;
+
+            fps
+        })
+    }
+}
@@ -0,0 +1,120 @@
+//! Raw escape-time data, for coloring renders with external tools.
+//!
+//! # File format
+//! The data is written as two files: a binary file containing the samples, and a JSON sidecar describing them.
+//!
+//! The binary file contains `width * height` little-endian `f32` samples per channel, without any header or padding.
+//! Channels are stored one after another (planar), in the order listed in the sidecar. Within a channel,
+//! samples are stored row by row, starting at the top left corner of the image.
+//!
+//! | Channel              | Description                                                                                     |
+//! |----------------------|-------------------------------------------------------------------------------------------------|
+//! | `smooth_iterations`  | The fractional iteration count at which the orbit escaped. Equals `max_iterations` inside the set. |
+//! | `distance_estimate`  | The estimated distance to the set on the complex plane. `0` inside the set.                      |
+//!
+//! The sidecar contains the image size, the channel names, the size of a pixel on the complex plane (`null` if it is
+//! out of the range of `f64`) and the view descriptor as key-value pairs (see [`crate::ViewDescriptor::to_key_values`]).
+
+use std::io::Write;
+
+use anyhow::Result;
+
+use crate::ViewDescriptor;
+
+/// The escape-time data of every pixel of a frame, see the [module documentation](self)
+pub struct IterationData {
+    pub width: u32,
+    pub height: u32,
+    /// Smooth iteration counts, row by row starting at the top
+    pub iterations: Vec<f32>,
+    /// Distance estimates, row by row starting at the top
+    pub distances: Vec<f32>,
+    /// The view this data was calculated for
+    pub view: ViewDescriptor,
+}
+
+impl IterationData {
+    pub const CHANNELS: [&'static str; 2] = ["smooth_iterations", "distance_estimate"];
+
+    /// The width and height of a pixel on the complex plane. In `f64`, as `f32` overflows beyond zoom exponents of about 38.
+    pub fn pixel_size(&self) -> f64 {
+        2.0 * 10.0_f64.powf(-self.view.viewport.zoom_exponent as f64) / self.width as f64
+    }
+
+    /// Writes the samples in the binary format described in the [module documentation](self)
+    pub fn write_raw(&self, mut writer: impl Write) -> Result<()> {
+        for channel in [&self.iterations, &self.distances] {
+            for sample in channel {
+                writer.write_all(&sample.to_le_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the JSON sidecar describing the binary file
+    pub fn write_sidecar(&self, mut writer: impl Write) -> Result<()> {
+        let channels = Self::CHANNELS.map(json_string).join(", ");
+        let view = self
+            .view
+            .to_key_values()
+            .into_iter()
+            .map(|(key, value)| format!("    {}: {}", json_string(key), json_string(&value)))
+            .collect::<Vec<_>>()
+            .join(",\n");
+
+        writeln!(writer, "{{")?;
+        writeln!(writer, "  \"format\": \"mandelbrot-iteration-data\",")?;
+        writeln!(writer, "  \"version\": 1,")?;
+        writeln!(writer, "  \"width\": {},", self.width)?;
+        writeln!(writer, "  \"height\": {},", self.height)?;
+        writeln!(writer, "  \"sample_type\": \"f32le\",")?;
+        writeln!(writer, "  \"layout\": \"planar\",")?;
+        writeln!(writer, "  \"channels\": [{channels}],")?;
+        writeln!(writer, "  \"max_iterations\": {},", self.view.shader_constants.max_iterations)?;
+        // JSON has no infinity or NaN
+        let pixel_size = self.pixel_size();
+        if pixel_size.is_finite() {
+            writeln!(writer, "  \"pixel_size\": {pixel_size},")?;
+        } else {
+            writeln!(writer, "  \"pixel_size\": null,")?;
+        }
+        writeln!(writer, "  \"view\": {{\n{view}\n  }}")?;
+        writeln!(writer, "}}")?;
+
+        Ok(())
+    }
+
+    /// Saves the data to `<path>.bin` and the sidecar to `<path>.json`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_raw(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        use std::{fs::File, io::BufWriter};
+
+        // Appended rather than replaced, so that e.g. `run.v2` becomes `run.v2.bin`
+        let with_extension = |extension: &str| {
+            let mut path = path.as_ref().as_os_str().to_owned();
+            path.push(extension);
+            std::path::PathBuf::from(path)
+        };
+        self.write_raw(BufWriter::new(File::create(with_extension(".bin"))?))?;
+        self.write_sidecar(BufWriter::new(File::create(with_extension(".json"))?))?;
+
+        Ok(())
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+
+    escaped
+}
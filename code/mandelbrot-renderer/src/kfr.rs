@@ -0,0 +1,228 @@
+//! Kalles Fraktaler parameter files (`.kfr`), the usual way deep-zoom locations are shared.
+//!
+//! A `.kfr` file consists of `Key: Value` lines. The center is stored as decimal numbers with as many digits as the zoom
+//! needs. These are kept as they are, so that locations survive an import and export unchanged, even though a
+//! [`Viewport`] only has `f32` precision. Keys without an equivalent here, e.g. the coloring method or slope shading,
+//! are kept in [`KfrLocation::other`] and written back.
+
+use std::io::Write;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::{
+    color::linear_to_srgb, Fractal, Palette, PaletteInterpolation, Polynomial, ShaderConstants, ViewDescriptor, Viewport,
+    DEFAULT_PALETTE_NAME,
+};
+
+/// The name of palettes created by [`KfrLocation::palette`]
+pub const KFR_PALETTE_NAME: &str = "kalles-fraktaler";
+
+/// The contents of a `.kfr` file, see the [module documentation](self)
+#[derive(Clone, Debug, PartialEq)]
+pub struct KfrLocation {
+    /// The real part of the center, a decimal number of arbitrary precision
+    pub re: String,
+    /// The imaginary part of the center, a decimal number of arbitrary precision
+    pub im: String,
+    /// The base-10 logarithm of the zoom. Kalles Fraktaler shows a vertical radius of `2 / zoom` around the center.
+    pub zoom_exponent: f64,
+    pub iterations: u32,
+    /// 8-bit sRGB, empty if the file has no colors
+    pub colors: Vec<[u8; 3]>,
+    /// The index into `colors` of the color for zero iterations
+    pub color_offset: usize,
+    /// All other keys and values, in the order of the file
+    pub other: Vec<(String, String)>,
+}
+
+impl KfrLocation {
+    pub fn parse(source: &str) -> Result<Self> {
+        let (mut re, mut im, mut zoom_exponent) = (None, None, None);
+        let mut location = Self {
+            re: String::new(),
+            im: String::new(),
+            zoom_exponent: 0.0,
+            iterations: ShaderConstants::default().max_iterations,
+            colors: Vec::new(),
+            color_offset: 0,
+            other: Vec::new(),
+        };
+
+        for line in source.lines().filter(|line| !line.trim().is_empty()) {
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| anyhow!("Expected `Key: Value`, not `{line}`"))?;
+            let (key, value) = (key.trim(), value.trim());
+            let invalid = || format!("Invalid value `{value}` for `{key}`");
+
+            match key {
+                "Re" => re = Some(parse_decimal(value).with_context(invalid)?),
+                "Im" => im = Some(parse_decimal(value).with_context(invalid)?),
+                "Zoom" => zoom_exponent = Some(parse_zoom_exponent(value).with_context(invalid)?),
+                "Iterations" => location.iterations = value.parse().with_context(invalid)?,
+                "Colors" => location.colors = parse_colors(value).with_context(invalid)?,
+                "ColorOffset" => location.color_offset = value.parse().with_context(invalid)?,
+                _ => location.other.push((key.to_owned(), value.to_owned())),
+            }
+        }
+
+        let (Some(re), Some(im), Some(zoom_exponent)) = (re, im, zoom_exponent) else {
+            bail!("The location is missing `Re`, `Im` or `Zoom`");
+        };
+        location.re = re;
+        location.im = im;
+        location.zoom_exponent = zoom_exponent;
+
+        Ok(location)
+    }
+
+    /// Describes the view of `view`, with the colors of `palette`.
+    /// `aspect_ratio` is the width divided by the height of the image the view is shown in.
+    pub fn from_view_descriptor(view: &ViewDescriptor, palette: &Palette, aspect_ratio: f32) -> Self {
+        Self {
+            re: view.viewport.center.0.to_string(),
+            im: view.viewport.center.1.to_string(),
+            zoom_exponent: view.viewport.zoom_exponent as f64 + (2.0 * aspect_ratio as f64).log10(),
+            iterations: view.shader_constants.max_iterations,
+            colors: palette
+                .colors()
+                .iter()
+                .map(|color| color.map(linear_to_srgb))
+                .collect(),
+            color_offset: 0,
+            other: Vec::new(),
+        }
+    }
+
+    /// Converts the location into a view descriptor, losing the precision of the center beyond `f32`.
+    /// `aspect_ratio` is the width divided by the height of the image the view is shown in.
+    pub fn to_view_descriptor(&self, aspect_ratio: f32) -> Result<ViewDescriptor> {
+        let center = self.center()?;
+        let shader_constants = ShaderConstants {
+            max_iterations: self.iterations,
+            ..ShaderConstants::default()
+        };
+        let palette = if self.colors.is_empty() {
+            DEFAULT_PALETTE_NAME
+        } else {
+            KFR_PALETTE_NAME
+        };
+
+        Ok(ViewDescriptor {
+            viewport: Viewport::new(
+                (center.0 as f32, center.1 as f32),
+                (self.zoom_exponent - (2.0 * aspect_ratio as f64).log10()) as f32,
+            ),
+            fractal: Fractal::default(),
+            polynomial: Polynomial::default(),
+            step_function: None,
+            palette: palette.to_owned(),
+            palette_interpolation: PaletteInterpolation::default(),
+            shader_constants,
+        })
+    }
+
+    /// The center with `f64` precision, e.g. for [`crate::CpuRenderer`]
+    pub fn center(&self) -> Result<(f64, f64)> {
+        Ok((self.re.parse()?, self.im.parse()?))
+    }
+
+    /// The colors as a palette with one entry per iteration, starting at the color offset.
+    /// Kalles Fraktaler allows more colors than the shader supports, which are thinned out evenly.
+    /// Without colors, this is the built-in palette.
+    pub fn palette(&self) -> Result<Palette> {
+        if self.colors.is_empty() {
+            return Ok(Palette::default());
+        }
+
+        let length = self.colors.len().min(Palette::MAX_COLORS);
+        // Reduced first, as the offset comes straight from the file and could overflow
+        let color_offset = self.color_offset % self.colors.len();
+        let colors: Vec<[u8; 3]> = (0..length)
+            .map(|i| self.colors[(i * self.colors.len() / length + color_offset) % self.colors.len()])
+            .collect();
+        Palette::from_srgb(KFR_PALETTE_NAME, &colors)
+    }
+
+    pub fn write(&self, mut writer: impl Write) -> Result<()> {
+        writeln!(writer, "Re: {}", self.re)?;
+        writeln!(writer, "Im: {}", self.im)?;
+        writeln!(writer, "Zoom: {}", format_zoom(self.zoom_exponent))?;
+        writeln!(writer, "Iterations: {}", self.iterations)?;
+        if !self.colors.is_empty() {
+            let colors: String = self
+                .colors
+                .iter()
+                .map(|[r, g, b]| format!("{r},{g},{b},"))
+                .collect();
+            writeln!(writer, "Colors: {colors}")?;
+        }
+        writeln!(writer, "ColorOffset: {}", self.color_offset)?;
+        for (key, value) in &self.other {
+            writeln!(writer, "{key}: {value}")?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&source).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write(file)
+    }
+}
+
+/// Checks that a value is a decimal number, and keeps all of its digits
+fn parse_decimal(value: &str) -> Result<String> {
+    if !value.parse::<f64>()?.is_finite() {
+        bail!("The number is not finite");
+    }
+    Ok(value.to_owned())
+}
+
+/// Parses the base-10 logarithm of numbers like `1.5E1000`, which are far outside the range of `f64`
+fn parse_zoom_exponent(value: &str) -> Result<f64> {
+    let (mantissa, exponent) = value.split_once(['E', 'e']).unwrap_or((value, "0"));
+    let mantissa: f64 = mantissa.parse()?;
+    let exponent: i64 = exponent.trim_start_matches('+').parse()?;
+    if !(mantissa > 0.0 && mantissa.is_finite()) {
+        bail!("The zoom has to be positive");
+    }
+
+    Ok(mantissa.log10() + exponent as f64)
+}
+
+/// The inverse of [`parse_zoom_exponent`], with as many digits as `f64` logarithms keep at deep zooms
+fn format_zoom(zoom_exponent: f64) -> String {
+    let mut exponent = zoom_exponent.floor();
+    let mut mantissa = format!("{:.12}", 10.0_f64.powf(zoom_exponent - exponent));
+    // Rounding can carry over into the exponent
+    if mantissa.starts_with("10") {
+        mantissa = format!("{:.12}", 1.0);
+        exponent += 1.0;
+    }
+
+    format!("{}E{exponent}", mantissa.trim_end_matches('0').trim_end_matches('.'))
+}
+
+/// Parses `r,g,b,r,g,b,...`, with an optional trailing comma
+fn parse_colors(value: &str) -> Result<Vec<[u8; 3]>> {
+    let channels = value
+        .split(',')
+        .map(str::trim)
+        .filter(|channel| !channel.is_empty())
+        .map(str::parse)
+        .collect::<Result<Vec<u8>, _>>()?;
+    if channels.len() % 3 != 0 {
+        bail!("Expected three channels per color, got {} channels", channels.len());
+    }
+
+    Ok(channels.chunks_exact(3).map(|color| [color[0], color[1], color[2]]).collect())
+}
@@ -1,17 +1,71 @@
-use std::{mem, sync::Arc};
+use std::{collections::HashMap, mem, sync::Arc};
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 use web_time::{Duration, Instant};
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
 use bytemuck::{Pod, Zeroable};
 use cgmath::{ElementWise, Vector2};
 use wgpu::{
     include_wgsl, util::{BufferInitDescriptor, DeviceExt}, vertex_attr_array, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BufferDescriptor, BufferUsages, ColorTargetState, ColorWrites, FragmentState, InstanceDescriptor, InstanceFlags, MultisampleState, PipelineCompilationOptions, PipelineLayoutDescriptor, PrimitiveState, RenderPipelineDescriptor, ShaderStages, Surface, SurfaceTarget, VertexBufferLayout, VertexState
 };
 
+mod buddhabrot;
+pub use buddhabrot::*;
+
+mod capture;
+pub use capture::*;
+
+mod color;
+pub use color::*;
+
+mod config;
+pub use config::*;
+
+mod contour_export;
+pub use contour_export::*;
+
+mod cpu;
+pub use cpu::*;
+
+mod heightfield;
+pub use heightfield::*;
+
+mod hud;
+pub use hud::*;
+
+mod iteration_data;
+pub use iteration_data::*;
+
+mod kfr;
+pub use kfr::*;
+
+mod math;
+pub use math::*;
+
+mod newton;
+pub use newton::*;
+
+mod overlay;
+pub use overlay::*;
+
+mod palette;
+pub use palette::*;
+
+mod quality;
+pub use quality::*;
+
+mod step_function;
+pub use step_function::*;
+
+mod tiled_export;
+pub use tiled_export::*;
+
+mod viewport;
+pub use viewport::*;
+
 #[repr(C)]
-#[derive(Copy, Clone, Pod, Zeroable)]
+#[derive(Copy, Clone, PartialEq, Pod, Zeroable)]
 struct Camera {
     center: Vector2<f32>,
     size: Vector2<f32>,
@@ -34,7 +88,8 @@ impl Vertex {
 
 pub struct MandelbrotRenderer {
     instance: wgpu::Instance,
-    surface: wgpu::Surface<'static>,
+    /// `None` for headless renderers
+    surface: Option<wgpu::Surface<'static>>,
     surface_config: wgpu::SurfaceConfiguration,
     adapter: wgpu::Adapter,
     device: wgpu::Device,
@@ -45,9 +100,78 @@ pub struct MandelbrotRenderer {
     vertex_buffer: wgpu::Buffer,
 
     camera_buffer: wgpu::Buffer,
+    /// The roots for [`Fractal::Newton`], in the same bind group as the camera
+    polynomial_buffer: wgpu::Buffer,
+    palette_buffer: wgpu::Buffer,
     camera_bind_group: wgpu::BindGroup,
 
-    render_pipeline: wgpu::RenderPipeline,
+    /// The format frames are rendered in. This may be an sRGB view format of the surface.
+    render_format: wgpu::TextureFormat,
+    /// Whether the fragment shader has to apply the sRGB transfer function itself, because `render_format` is linear
+    encode_srgb: bool,
+
+    /// The number of samples per pixel. If this is greater than 1, frames are rendered into `multisample_view` first.
+    sample_count: u32,
+    multisample_view: Option<wgpu::TextureView>,
+
+    render_pipeline_layout: wgpu::PipelineLayout,
+    vertex_shader: wgpu::ShaderModule,
+    fragment_shader: wgpu::ShaderModule,
+
+    shader_constants: ShaderConstants,
+    fractal: Fractal,
+    polynomial: Polynomial,
+    palette: Palette,
+    step_function: Option<StepFunction>,
+    /// Render pipelines are created lazily for every combination of shader constants and render target that is used
+    render_pipelines: HashMap<PipelineKey, wgpu::RenderPipeline>,
+
+    /// The camera of the most recent frame
+    last_camera: Camera,
+
+    quality_policy: QualityPolicy,
+    quality_controller: QualityController,
+    /// Created once the first frame is rendered at a lower resolution
+    upscaler: Option<Upscaler>,
+    /// The resolution scale of the most recent frame
+    render_scale: f32,
+    /// Created once an overlay is set
+    overlay_renderer: Option<OverlayRenderer>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+struct PipelineKey {
+    shader_constants: ShaderConstants,
+    fractal: Fractal,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    encode_srgb: bool,
+    output: PipelineOutput,
+}
+
+/// What the fragment shader writes into the render target
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+enum PipelineOutput {
+    Color,
+    /// The smooth iteration count and distance estimate as floats
+    IterationData,
+}
+
+impl PipelineOutput {
+    fn entry_point(&self) -> &'static str {
+        match self {
+            PipelineOutput::Color => "main",
+            PipelineOutput::IterationData => "iteration_data",
+        }
+    }
+
+    /// The texture format used when rendering this output into a texture for reading it back
+    fn offscreen_format(&self) -> wgpu::TextureFormat {
+        match self {
+            PipelineOutput::Color => wgpu::TextureFormat::Rgba8UnormSrgb,
+            PipelineOutput::IterationData => wgpu::TextureFormat::Rg32Float,
+        }
+    }
 }
 
 impl MandelbrotRenderer {
@@ -55,34 +179,44 @@ impl MandelbrotRenderer {
     pub async fn new_from_canvas(
         size: (u32, u32),
         canvas: web_sys::HtmlCanvasElement,
-        vsync: bool,
+        config: RendererConfig,
     ) -> Result<Self> {
-        Self::new(size, SurfaceTarget::Canvas(canvas), vsync).await
+        Self::new(size, SurfaceTarget::Canvas(canvas), config).await
     }
 
     pub async fn new(
         size: (u32, u32),
         surface: impl Into<wgpu::SurfaceTarget<'static>>,
-        vsync: bool,
+        config: RendererConfig,
     ) -> Result<Self> {
-        let instance = wgpu::Instance::new(InstanceDescriptor {
-            #[cfg(target_arch = "wasm32")]
-            backends: wgpu::Backends::GL,
-            #[cfg(not(target_arch = "wasm32"))]
-            backends: wgpu::Backends::PRIMARY,
-            ..Default::default()
-        });
+        let instance = create_instance(&config);
+        let surface = instance.create_surface(surface)?;
+
+        Self::with_surface(instance, Some(surface), size, config).await
+    }
 
-        let surface = instance.create_surface(surface).unwrap();
+    /// Creates a renderer that does not draw into a window, e.g. for exporting images.
+    /// [`MandelbrotRenderer::render`] only updates the camera, frames have to be read back with the capture and export functions.
+    pub async fn new_headless(config: RendererConfig) -> Result<Self> {
+        let instance = create_instance(&config);
 
+        Self::with_surface(instance, None, (1, 1), config).await
+    }
+
+    async fn with_surface(
+        instance: wgpu::Instance,
+        surface: Option<wgpu::Surface<'static>>,
+        size: (u32, u32),
+        config: RendererConfig,
+    ) -> Result<Self> {
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
+                power_preference: config.power_preference(),
+                compatible_surface: surface.as_ref(),
+                force_fallback_adapter: config.force_fallback_adapter(),
             })
             .await
-            .unwrap();
+            .ok_or_else(|| anyhow!("No suitable graphics adapter found"))?;
 
         let (device, queue) = adapter
             .request_device(
@@ -97,36 +231,37 @@ impl MandelbrotRenderer {
                 },
                 None, // Trace path
             )
-            .await
-            .unwrap();
-
-        let surface_caps = surface.get_capabilities(&adapter);
-        // Shader code in this tutorial assumes an sRGB surface texture. Using a different
-        // one will result in all the colors coming out darker. If you want to support non
-        // sRGB surfaces, you'll need to account for that when drawing to the frame.
-        let surface_format = surface_caps
-            .formats
-            .iter()
-            .find(|f| f.is_srgb())
-            .copied()
-            .unwrap_or(surface_caps.formats[0]);
-
-        let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width: size.0,
-            height: size.1,
-            present_mode: if vsync {
-                wgpu::PresentMode::AutoVsync
-            } else {
-                wgpu::PresentMode::AutoNoVsync
-            },
-            alpha_mode: surface_caps.alpha_modes[0],
-            view_formats: vec![],
-            desired_maximum_frame_latency: 2,
+            .await?;
+
+        let (surface_config, render_format, encode_srgb) = match &surface {
+            Some(surface) => configure_surface(surface, &adapter, &device, size, &config)?,
+            // Without a surface, the configuration only keeps track of the size
+            None => {
+                let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+                let surface_config = wgpu::SurfaceConfiguration {
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    format,
+                    width: size.0,
+                    height: size.1,
+                    present_mode: config.present_mode(),
+                    alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+                    view_formats: vec![],
+                    desired_maximum_frame_latency: config.desired_maximum_frame_latency(),
+                };
+                (surface_config, format, false)
+            }
         };
 
-        surface.configure(&device, &surface_config);
+        let sample_count = config.sample_count();
+        if !adapter
+            .get_texture_format_features(render_format)
+            .flags
+            .sample_count_supported(sample_count)
+        {
+            bail!("A sample count of {sample_count} is not supported for the surface format {render_format:?}");
+        }
+
+        let multisample_view = create_multisample_view(&device, &surface_config, render_format, sample_count);
 
         let camera_buffer = device.create_buffer(&BufferDescriptor {
             label: None,
@@ -134,12 +269,22 @@ impl MandelbrotRenderer {
             usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
             mapped_at_creation: false, 
         });
+        let polynomial_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&[config.polynomial().to_uniform()]),
+            usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
+        });
+        let palette_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&[config.palette().to_uniform()]),
+            usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
+        });
 
         let camera_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: None,
-                entries: &[BindGroupLayoutEntry {
-                    binding: 0,
+                entries: &[0, 1, 2].map(|binding| BindGroupLayoutEntry {
+                    binding,
                     visibility: ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
@@ -147,15 +292,25 @@ impl MandelbrotRenderer {
                         min_binding_size: None,
                     },
                     count: None,
-                }],
+                }),
             });
         let camera_bind_group = device.create_bind_group(&BindGroupDescriptor {
             label: None,
             layout: &camera_bind_group_layout,
-            entries: &[BindGroupEntry {
-                binding: 0,
-                resource: camera_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: polynomial_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: palette_buffer.as_entire_binding(),
+                },
+            ],
         });
 
         let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
@@ -165,7 +320,7 @@ impl MandelbrotRenderer {
         });
 
         let vertex_shader = device.create_shader_module(include_wgsl!("shaders/vert.wgsl"));
-        let fragment_shader = device.create_shader_module(include_wgsl!("shaders/frag.wgsl"));
+        let fragment_shader = create_fragment_shader(&device, config.step_function());
 
         let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: None,
@@ -173,39 +328,6 @@ impl MandelbrotRenderer {
             usage: BufferUsages::VERTEX,
         });
 
-        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&render_pipeline_layout),
-            vertex: VertexState {
-                module: &vertex_shader,
-                entry_point: "main",
-                buffers: &[Vertex::layout()],
-                compilation_options: PipelineCompilationOptions::default(),
-            },
-            primitive: PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None,
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: MultisampleState::default(),
-            fragment: Some(FragmentState {
-                module: &fragment_shader,
-                entry_point: "main",
-                compilation_options: PipelineCompilationOptions::default(),
-                targets: &[Some(ColorTargetState {
-                    format: surface_config.format,
-                    blend: None,
-                    write_mask: ColorWrites::all(),
-                })],
-            }),
-            multiview: None,
-        });
-
         Ok(Self {
             instance,
             surface,
@@ -215,26 +337,154 @@ impl MandelbrotRenderer {
             current_window_size: size,
             vertex_buffer,
             camera_buffer,
+            polynomial_buffer,
+            palette_buffer,
             camera_bind_group,
-            render_pipeline,
+            render_pipeline_layout,
+            vertex_shader,
+            fragment_shader,
+            shader_constants: config.shader_constants(),
+            fractal: config.fractal(),
+            polynomial: config.polynomial(),
+            palette: config.palette().clone(),
+            step_function: config.step_function().cloned(),
+            render_pipelines: HashMap::new(),
+            last_camera: Camera {
+                center: Vector2::new(0.0, 0.0),
+                size: Vector2::new(1.0, 1.0),
+            },
             surface_config,
+            render_format,
+            encode_srgb,
+            sample_count,
+            multisample_view,
+            quality_policy: config.quality_policy(),
+            quality_controller: QualityController::new(),
+            upscaler: None,
+            render_scale: 1.0,
+            overlay_renderer: None,
         })
     }
 
+    pub fn shader_constants(&self) -> ShaderConstants {
+        self.shader_constants
+    }
+
+    /// Changes the shader constants used for subsequent frames.
+    /// The pipeline for these constants is created on the next render, if it does not exist yet.
+    pub fn set_shader_constants(&mut self, shader_constants: ShaderConstants) {
+        self.shader_constants = shader_constants;
+    }
+
+    pub fn fractal(&self) -> Fractal {
+        self.fractal
+    }
+
+    /// Changes the fractal rendered in subsequent frames. Like shader constants, each fractal has its own pipelines.
+    pub fn set_fractal(&mut self, fractal: Fractal) {
+        self.fractal = fractal;
+    }
+
+    pub fn polynomial(&self) -> Polynomial {
+        self.polynomial
+    }
+
+    /// Changes the polynomial of [`Fractal::Newton`]. This only updates a uniform buffer, so it is cheap enough for every frame.
+    pub fn set_polynomial(&mut self, polynomial: Polynomial) {
+        self.polynomial = polynomial;
+        self.queue
+            .write_buffer(&self.polynomial_buffer, 0, bytemuck::cast_slice(&[polynomial.to_uniform()]));
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// Changes the colors of subsequent frames. Like [`Self::set_polynomial`], this only updates a uniform buffer.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.queue
+            .write_buffer(&self.palette_buffer, 0, bytemuck::cast_slice(&[palette.to_uniform()]));
+        self.palette = palette;
+    }
+
+    pub fn step_function(&self) -> Option<&StepFunction> {
+        self.step_function.as_ref()
+    }
+
+    /// Changes the iteration step of [`Fractal::Custom`], `None` restores the built-in one.
+    /// This recompiles the fragment shader and discards all pipelines.
+    pub fn set_step_function(&mut self, step_function: Option<StepFunction>) {
+        self.fragment_shader = create_fragment_shader(&self.device, step_function.as_ref());
+        self.render_pipelines.clear();
+        self.step_function = step_function;
+    }
+
+    pub fn quality_policy(&self) -> QualityPolicy {
+        self.quality_policy
+    }
+
+    pub fn set_quality_policy(&mut self, quality_policy: QualityPolicy) {
+        self.quality_policy = quality_policy;
+    }
+
+    /// Tells the quality policy that the user is dragging, zooming or otherwise changing the view.
+    /// Frames are rendered at a lower resolution until the settle delay of the policy has passed without another call.
+    pub fn mark_interaction(&mut self) {
+        self.quality_controller.mark_interaction();
+    }
+
+    /// The resolution scale (from 0 to 1, per axis) the most recent frame was rendered at, see [`QualityPolicy`]
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale
+    }
+
+    /// Sets an image that is drawn over every frame until it is replaced, or removes it with `None`.
+    /// Overlays are not part of captured frames.
+    pub fn set_overlay(&mut self, overlay: Option<&Overlay>) {
+        if overlay.is_none() && self.overlay_renderer.is_none() {
+            return;
+        }
+
+        let overlay_renderer = self.overlay_renderer.get_or_insert_with(|| {
+            OverlayRenderer::new(&self.device, self.render_format, self.encode_srgb, Vertex::layout())
+        });
+        overlay_renderer.set_overlay(&self.device, &self.queue, overlay);
+    }
+
     pub fn render(&mut self, camera_center: (f32, f32), camera_size: (f32, f32)) {
         // Update camera
         let camera = Camera {
             center: Vector2::from(camera_center),
             size: Vector2::from(camera_size),
         };
+        let camera_moved = camera != self.last_camera;
+        self.last_camera = camera;
         self.queue
             .write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[camera]));
 
-        // Get render target texture
-        let output = self.surface.get_current_texture().unwrap();
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        self.render_scale = self
+            .quality_controller
+            .frame_scale(&self.quality_policy, camera_moved);
+        let render_size = scaled_size(self.current_window_size, self.render_scale);
+        let upscale = render_size != self.current_window_size;
+
+        // Upscaled frames are rendered without MSAA
+        let pipeline_key = self.prepare_pipeline(
+            self.render_format,
+            if upscale { 1 } else { self.sample_count },
+            self.encode_srgb,
+            PipelineOutput::Color,
+        );
+
+        // Get render target texture. Headless renderers have none, and only keep track of the camera.
+        let Some(surface) = &self.surface else {
+            return;
+        };
+        let output = surface.get_current_texture().unwrap();
+        let view = output.texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(self.render_format),
+            ..Default::default()
+        });
 
         // Render to texture
         let mut encoder = self
@@ -242,32 +492,31 @@ impl MandelbrotRenderer {
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("Render Encoder"),
             });
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
-
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
-
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.draw(0..(QUAD_VERTS.len() as u32), 0..1);
+        if upscale {
+            // Render at a lower resolution and stretch the frame over the surface
+            let mut upscaler = self
+                .upscaler
+                .take()
+                .unwrap_or_else(|| Upscaler::new(&self.device, self.render_format, Vertex::layout()));
+            self.draw(&mut encoder, pipeline_key, upscaler.target_view(&self.device, render_size), None);
+            upscaler.draw(&mut encoder, &view, &self.vertex_buffer, QUAD_VERTS.len() as u32);
+            self.upscaler = Some(upscaler);
+        } else {
+            // With MSAA, render into the multisampled texture and resolve it into the surface
+            match &self.multisample_view {
+                Some(multisample_view) => self.draw(&mut encoder, pipeline_key, multisample_view, Some(&view)),
+                None => self.draw(&mut encoder, pipeline_key, &view, None),
+            }
+        }
+        if let Some(overlay_renderer) = &self.overlay_renderer {
+            overlay_renderer.draw(
+                &mut encoder,
+                &self.queue,
+                &view,
+                self.current_window_size,
+                &self.vertex_buffer,
+                QUAD_VERTS.len() as u32,
+            );
         }
 
         // Submit and present
@@ -275,16 +524,430 @@ impl MandelbrotRenderer {
         output.present();
     }
 
+    /// Reads back the most recently rendered frame as 8-bit sRGB pixels.
+    ///
+    /// The frame is rendered again with the same camera into an offscreen texture, as surface textures can not be read from on all platforms.
+    pub async fn capture_frame(&mut self) -> Result<CapturedFrame> {
+        let size = self.current_window_size;
+        let pixels = self
+            .render_offscreen(self.last_camera, size, PipelineOutput::Color)
+            .await?;
+
+        Ok(CapturedFrame {
+            width: size.0,
+            height: size.1,
+            pixels,
+            view: self.view_descriptor(),
+        })
+    }
+
+    /// Reads back the smooth iteration count and distance estimate of every pixel of the most recently rendered frame.
+    /// The resolution can be chosen freely, up to the maximum texture size of the device.
+    pub async fn capture_iteration_data(&mut self, (width, height): (u32, u32)) -> Result<IterationData> {
+        if width == 0 || height == 0 {
+            bail!("The captured iteration data must not be empty");
+        }
+        let max_texture_size = self.device.limits().max_texture_dimension_2d;
+        if width > max_texture_size || height > max_texture_size {
+            bail!("{width}x{height} pixels exceed the maximum texture size of {max_texture_size}");
+        }
+
+        // Keep the same horizontal extent, and adjust the vertical one to the new aspect ratio
+        let mut camera = self.last_camera;
+        camera.size.y = camera.size.x * height as f32 / width as f32;
+
+        let data = self
+            .render_offscreen(camera, (width, height), PipelineOutput::IterationData)
+            .await?;
+
+        let (iterations, distances) = data
+            .chunks_exact(mem::size_of::<[f32; 2]>())
+            .map(|texel| {
+                let [iterations, distance] = bytemuck::pod_read_unaligned::<[f32; 2]>(texel);
+                (iterations, distance)
+            })
+            .unzip();
+
+        Ok(IterationData {
+            width,
+            height,
+            iterations,
+            distances,
+            view: self.view_descriptor(),
+        })
+    }
+
+    /// Renders a single frame with the given camera into a new texture, and reads that texture back
+    async fn render_offscreen(
+        &mut self,
+        camera: Camera,
+        (width, height): (u32, u32),
+        output: PipelineOutput,
+    ) -> Result<Vec<u8>> {
+        let format = output.offscreen_format();
+
+        self.queue
+            .write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[camera]));
+        let pipeline_key = self.prepare_pipeline(format, 1, false, output);
+
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Offscreen Encoder"),
+            });
+        self.draw(&mut encoder, pipeline_key, &view, None);
+
+        read_texture(&self.device, &self.queue, encoder, &texture).await
+    }
+
+    /// A CPU renderer with the same settings, e.g. to look up the iterations of single points
+    pub fn cpu_renderer(&self) -> CpuRenderer {
+        CpuRenderer::new(self.shader_constants)
+            .with_fractal(self.fractal)
+            .with_polynomial(self.polynomial)
+            .with_palette(self.palette.clone())
+    }
+
+    /// Describes the most recently rendered view
+    pub fn view_descriptor(&self) -> ViewDescriptor {
+        self.view_descriptor_for(Viewport::new(
+            self.last_camera.center.into(),
+            -self.last_camera.size.x.log10(),
+        ))
+    }
+
+    /// Describes the given view, rendered with the current settings
+    pub(crate) fn view_descriptor_for(&self, viewport: Viewport) -> ViewDescriptor {
+        ViewDescriptor {
+            viewport,
+            fractal: self.fractal,
+            polynomial: self.polynomial,
+            step_function: self.step_function.clone(),
+            palette: self.palette.name().to_owned(),
+            palette_interpolation: self.palette.interpolation(),
+            shader_constants: self.shader_constants,
+        }
+    }
+
+    /// Creates the pipeline for the current shader constants and the given render target, if it does not exist yet
+    fn prepare_pipeline(
+        &mut self,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        encode_srgb: bool,
+        output: PipelineOutput,
+    ) -> PipelineKey {
+        let key = PipelineKey {
+            shader_constants: self.shader_constants,
+            fractal: self.fractal,
+            format,
+            sample_count,
+            encode_srgb,
+            output,
+        };
+
+        self.render_pipelines.entry(key).or_insert_with(|| {
+            create_render_pipeline(
+                &self.device,
+                &self.render_pipeline_layout,
+                &self.vertex_shader,
+                &self.fragment_shader,
+                key,
+            )
+        });
+
+        key
+    }
+
+    /// Records a render pass that draws the fractal into the given view
+    fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline_key: PipelineKey,
+        view: &wgpu::TextureView,
+        resolve_target: Option<&wgpu::TextureView>,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: 0.1,
+                        g: 0.2,
+                        b: 0.3,
+                        a: 1.0,
+                    }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(&self.render_pipelines[&pipeline_key]);
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..(QUAD_VERTS.len() as u32), 0..1);
+    }
+
     pub fn resize(&mut self, (width, height): (u32, u32)) {
         if width > 0 && height > 0 {
             self.current_window_size = (width, height);
             self.surface_config.width = width;
             self.surface_config.height = height;
-            self.surface.configure(&self.device, &self.surface_config);
+            if let Some(surface) = &self.surface {
+                surface.configure(&self.device, &self.surface_config);
+            }
+            self.multisample_view = create_multisample_view(&self.device, &self.surface_config, self.render_format, self.sample_count);
         }
     }
 }
 
+fn create_fragment_shader(device: &wgpu::Device, step_function: Option<&StepFunction>) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("shaders/frag.wgsl"),
+        source: wgpu::ShaderSource::Wgsl(fragment_shader_source(step_function)),
+    })
+}
+
+fn create_instance(config: &RendererConfig) -> wgpu::Instance {
+    wgpu::Instance::new(InstanceDescriptor {
+        backends: config.backends(),
+        ..Default::default()
+    })
+}
+
+/// Chooses the formats and presentation options for the surface, and configures it.
+/// Returns the configuration, the format frames are rendered in and whether the shader has to encode colors as sRGB.
+fn configure_surface(
+    surface: &wgpu::Surface,
+    adapter: &wgpu::Adapter,
+    device: &wgpu::Device,
+    size: (u32, u32),
+    config: &RendererConfig,
+) -> Result<(wgpu::SurfaceConfiguration, wgpu::TextureFormat, bool)> {
+    let surface_caps = surface.get_capabilities(adapter);
+    // The shader calculates colors for an sRGB render target. Linear surfaces (which
+    // some browsers and backends only offer) are rendered through an sRGB view if possible,
+    // otherwise the shader applies the sRGB transfer function itself.
+    let surface_format = match surface_caps.formats.iter().find(|f| f.is_srgb()) {
+        Some(format) => *format,
+        None if config.non_srgb_surface() == NonSrgbSurface::Deny => {
+            bail!("The surface does not support any sRGB format")
+        }
+        None => *surface_caps
+            .formats
+            .first()
+            .context("The surface is not supported by the adapter")?,
+    };
+
+    let srgb_view_format = surface_format.add_srgb_suffix();
+    let supports_srgb_view = srgb_view_format != surface_format
+        && adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::SURFACE_VIEW_FORMATS);
+    let (render_format, encode_srgb) = match config.non_srgb_surface() {
+        _ if surface_format.is_srgb() => (surface_format, false),
+        NonSrgbSurface::Convert if supports_srgb_view => (srgb_view_format, false),
+        NonSrgbSurface::Passthrough => (surface_format, false),
+        _ => (surface_format, true),
+    };
+    if render_format != surface_format || encode_srgb {
+        log::info!("Using linear surface format {surface_format:?}, rendering as {render_format:?} (shader sRGB encoding: {encode_srgb})");
+    }
+
+    // Auto modes are always supported, as they fall back to Fifo themselves
+    let present_mode = match config.present_mode() {
+        mode @ (wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync) => mode,
+        mode if surface_caps.present_modes.contains(&mode) => mode,
+        mode => {
+            log::warn!("Present mode {mode:?} is not supported, falling back to Fifo");
+            wgpu::PresentMode::Fifo
+        }
+    };
+
+    let alpha_mode = config
+        .alpha_mode()
+        .filter(|mode| surface_caps.alpha_modes.contains(mode))
+        .unwrap_or(surface_caps.alpha_modes[0]);
+
+    let surface_config = wgpu::SurfaceConfiguration {
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        format: surface_format,
+        width: size.0,
+        height: size.1,
+        present_mode,
+        alpha_mode,
+        view_formats: if render_format != surface_format {
+            vec![render_format]
+        } else {
+            vec![]
+        },
+        desired_maximum_frame_latency: config.desired_maximum_frame_latency(),
+    };
+
+    surface.configure(device, &surface_config);
+
+    Ok((surface_config, render_format, encode_srgb))
+}
+
+fn create_render_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    vertex_shader: &wgpu::ShaderModule,
+    fragment_shader: &wgpu::ShaderModule,
+    key: PipelineKey,
+) -> wgpu::RenderPipeline {
+    let constants = key.shader_constants.to_pipeline_constants(key.fractal, key.encode_srgb);
+
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: None,
+        layout: Some(layout),
+        vertex: VertexState {
+            module: vertex_shader,
+            entry_point: "main",
+            buffers: &[Vertex::layout()],
+            compilation_options: PipelineCompilationOptions::default(),
+        },
+        primitive: PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            unclipped_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: MultisampleState {
+            count: key.sample_count,
+            ..Default::default()
+        },
+        fragment: Some(FragmentState {
+            module: fragment_shader,
+            entry_point: key.output.entry_point(),
+            compilation_options: PipelineCompilationOptions {
+                constants: &constants,
+                ..Default::default()
+            },
+            targets: &[Some(ColorTargetState {
+                format: key.format,
+                blend: None,
+                write_mask: ColorWrites::all(),
+            })],
+        }),
+        multiview: None,
+    })
+}
+
+/// Copies a texture into a buffer and reads it back to the CPU, after submitting all commands recorded in `encoder`.
+/// Returns the tightly packed texel data.
+async fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    mut encoder: wgpu::CommandEncoder,
+    texture: &wgpu::Texture,
+) -> Result<Vec<u8>> {
+    let (width, height) = (texture.width(), texture.height());
+    let bytes_per_texel = texture
+        .format()
+        .block_copy_size(None)
+        .context("Texture format can not be copied")?;
+
+    // Rows of the buffer have to be aligned
+    let unpadded_bytes_per_row = width * bytes_per_texel;
+    let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+        * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("Readback Buffer"),
+        size: (padded_bytes_per_row * height) as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let (sender, receiver) = futures_channel::oneshot::channel();
+    let slice = buffer.slice(..);
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    // Blocks until the buffer is mapped on native platforms. On the web, mapping happens in the background.
+    device.poll(wgpu::Maintain::Wait);
+    receiver.await??;
+
+    let data = slice
+        .get_mapped_range()
+        .chunks_exact(padded_bytes_per_row as usize)
+        .flat_map(|row| &row[..unpadded_bytes_per_row as usize])
+        .copied()
+        .collect();
+    buffer.unmap();
+
+    Ok(data)
+}
+
+/// Creates the texture that is rendered into when MSAA is enabled
+fn create_multisample_view(
+    device: &wgpu::Device,
+    surface_config: &wgpu::SurfaceConfiguration,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> Option<wgpu::TextureView> {
+    (sample_count > 1).then(|| {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Multisample Texture"),
+                size: wgpu::Extent3d {
+                    width: surface_config.width,
+                    height: surface_config.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    })
+}
+
 const QUAD_VERTS: &[Vertex] = &[
     Vertex(Vector2::new(-1.0, 1.0)),  // top left
     Vertex(Vector2::new(-1.0, -1.0)), // bot left
@@ -0,0 +1,261 @@
+//! Locating features of the Mandelbrot set with Newton's method.
+//!
+//! Nuclei are the centers of hyperbolic components (minibrots and bulbs), the parameters `c` for which
+//! the orbit of `0` is periodic: `z_p(c) = 0`. Misiurewicz points are the tips, spiral centers and branch points of the
+//! filaments, where the orbit only becomes periodic after a preperiod: `z_{q+p}(c) = z_q(c)`.
+//!
+//! Both equations are also solved by features with a lower (pre)period. These roots are divided out, so that Newton's
+//! method converges to a feature with exactly the requested (pre)period. The method and the size estimates
+//! follow Claude Heiland-Allen's articles on Newton's method for nuclei and Misiurewicz points.
+
+use std::ops::{Add, Div, Mul, Sub};
+
+/// The center of a hyperbolic component of the Mandelbrot set
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Nucleus {
+    pub position: (f64, f64),
+    pub period: u32,
+    /// The approximate radius of the component on the complex plane, relative to the main cardioid which has a size of 1
+    pub size: f64,
+}
+
+/// A point where the orbit of `0` becomes periodic after `preperiod` iterations
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MisiurewiczPoint {
+    pub position: (f64, f64),
+    pub preperiod: u32,
+    pub period: u32,
+    /// The approximate size of the surrounding structure. Close to the point, the set looks like the Julia set
+    /// of the point, scaled down by this factor.
+    pub size: f64,
+}
+
+/// Finds the period of the atom domain containing `position`: the iteration at which `|z|` was the smallest before escaping.
+/// Newton's method started at `position` usually converges to a nucleus of this period.
+pub fn atom_domain_period(position: (f64, f64), max_iterations: u32) -> Option<u32> {
+    let c = Complex::from(position);
+    let mut z = Complex::ZERO;
+    let mut min_norm = f64::INFINITY;
+    let mut period = None;
+
+    for i in 1..=max_iterations {
+        z = z * z + c;
+        let norm = z.norm_sqr();
+        if norm > 4.0 {
+            break;
+        }
+        if norm < min_norm {
+            min_norm = norm;
+            period = Some(i);
+        }
+    }
+
+    period
+}
+
+/// Finds the nucleus of exactly the given period that is closest to `start`, or `None` if Newton's method does not converge
+/// within `max_steps` steps.
+pub fn find_nucleus(start: (f64, f64), period: u32, max_steps: u32) -> Option<Nucleus> {
+    if period == 0 {
+        return None;
+    }
+
+    let position = newton(Complex::from(start), max_steps, |c| {
+        // Newton step for z_p / prod(z_k) over the proper divisors k of p
+        let (zs, dzs) = orbit(c, period);
+        if zs[period as usize] == Complex::ZERO {
+            return Complex::ZERO;
+        }
+        let mut derivative_ratio = dzs[period as usize] / zs[period as usize];
+        for k in (1..period).filter(|k| period.is_multiple_of(*k)) {
+            derivative_ratio = derivative_ratio - dzs[k as usize] / zs[k as usize];
+        }
+        Complex::ZERO - Complex::ONE / derivative_ratio
+    })?;
+
+    // Make sure the orbit does not return to 0 earlier
+    let (zs, _) = orbit(position, period);
+    let tolerance = 1e-9 * position.norm().max(1.0);
+    if zs[period as usize].norm() > tolerance || zs[1..period as usize].iter().any(|z| z.norm() <= tolerance) {
+        return None;
+    }
+
+    Some(Nucleus {
+        position: position.into(),
+        period,
+        size: nucleus_size(position.into(), period),
+    })
+}
+
+/// Estimates the size of the component with the given nucleus, see [`Nucleus::size`]
+pub fn nucleus_size(nucleus: (f64, f64), period: u32) -> f64 {
+    let c = Complex::from(nucleus);
+    let mut z = Complex::ZERO;
+    let mut l = Complex::ONE;
+    let mut b = Complex::ONE;
+
+    for _ in 1..period {
+        z = z * z + c;
+        l = Complex(2.0, 0.0) * z * l;
+        b = b + Complex::ONE / l;
+    }
+
+    1.0 / (b * l * l).norm()
+}
+
+/// Finds the Misiurewicz point with exactly the given preperiod and period that is closest to `start`,
+/// or `None` if Newton's method does not converge within `max_steps` steps.
+pub fn find_misiurewicz_point(start: (f64, f64), preperiod: u32, period: u32, max_steps: u32) -> Option<MisiurewiczPoint> {
+    if period == 0 {
+        return None;
+    }
+    let (q, p) = (preperiod as usize, period as usize);
+
+    let position = newton(Complex::from(start), max_steps, |c| {
+        // Newton step for (z_{q+p} - z_q) / prod(z_{i+p} - z_i) over i < q, and additionally over the
+        // proper divisors k of p for (z_{q+k} - z_q)
+        let (zs, dzs) = orbit(c, preperiod + period);
+        let difference = |i: usize, j: usize| (zs[i] - zs[j], dzs[i] - dzs[j]);
+
+        let (f, df) = difference(q + p, q);
+        if f == Complex::ZERO {
+            return Complex::ZERO;
+        }
+        let mut derivative_ratio = df / f;
+        for i in 0..q {
+            let (g, dg) = difference(i + p, i);
+            derivative_ratio = derivative_ratio - dg / g;
+        }
+        for k in (1..p).filter(|k| p.is_multiple_of(*k)) {
+            let (g, dg) = difference(q + k, q);
+            derivative_ratio = derivative_ratio - dg / g;
+        }
+        Complex::ZERO - Complex::ONE / derivative_ratio
+    })?;
+
+    // Make sure the orbit does not become periodic earlier, or with a shorter period
+    let (zs, dzs) = orbit(position, preperiod + period);
+    let tolerance = 1e-9 * zs[q].norm().max(1.0);
+    let is_periodic_after = |i: usize, period: usize| (zs[i + period] - zs[i]).norm() <= tolerance;
+    if !is_periodic_after(q, p)
+        || (0..q).any(|i| is_periodic_after(i, p))
+        || (1..p).any(|k| is_periodic_after(q, k))
+    {
+        return None;
+    }
+
+    Some(MisiurewiczPoint {
+        position: position.into(),
+        preperiod,
+        period,
+        size: 1.0 / dzs[q].norm(),
+    })
+}
+
+/// Iterates `c - step(c)` until the steps become negligible
+fn newton(mut c: Complex, max_steps: u32, step: impl Fn(Complex) -> Complex) -> Option<Complex> {
+    for _ in 0..max_steps {
+        let delta = step(c);
+        c = c + delta;
+
+        if !c.0.is_finite() || !c.1.is_finite() || c.norm_sqr() > 16.0 {
+            return None;
+        }
+        if delta.norm() <= 1e-15 * c.norm().max(1.0) {
+            return Some(c);
+        }
+    }
+
+    None
+}
+
+/// The orbit of `0` and the derivatives with respect to `c`, from `z_0` to `z_n`
+fn orbit(c: Complex, n: u32) -> (Vec<Complex>, Vec<Complex>) {
+    let mut zs = Vec::with_capacity(n as usize + 1);
+    let mut dzs = Vec::with_capacity(n as usize + 1);
+    let (mut z, mut dz) = (Complex::ZERO, Complex::ZERO);
+    zs.push(z);
+    dzs.push(dz);
+
+    for _ in 0..n {
+        dz = Complex(2.0, 0.0) * z * dz + Complex::ONE;
+        z = z * z + c;
+        zs.push(z);
+        dzs.push(dz);
+    }
+
+    (zs, dzs)
+}
+
+/// A minimal complex number in `f64`, shared with the other CPU calculations of this crate
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Complex(pub(crate) f64, pub(crate) f64);
+
+impl Complex {
+    pub(crate) const ZERO: Self = Self(0.0, 0.0);
+    pub(crate) const ONE: Self = Self(1.0, 0.0);
+
+    pub(crate) fn norm_sqr(self) -> f64 {
+        self.0 * self.0 + self.1 * self.1
+    }
+
+    pub(crate) fn norm(self) -> f64 {
+        self.0.hypot(self.1)
+    }
+
+    /// Only supports non-negative exponents
+    pub(crate) fn powi(self, exponent: i32) -> Self {
+        (0..exponent).fold(Self::ONE, |power, _| power * self)
+    }
+}
+
+impl From<(f64, f64)> for Complex {
+    fn from((re, im): (f64, f64)) -> Self {
+        Self(re, im)
+    }
+}
+
+impl From<Complex> for (f64, f64) {
+    fn from(Complex(re, im): Complex) -> Self {
+        (re, im)
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self(self.0 + other.0, self.1 + other.1)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self(self.0 - other.0, self.1 - other.1)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self(
+            self.0 * other.0 - self.1 * other.1,
+            self.0 * other.1 + self.1 * other.0,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        let norm_sqr = other.norm_sqr();
+        Self(
+            (self.0 * other.0 + self.1 * other.1) / norm_sqr,
+            (self.1 * other.0 - self.0 * other.1) / norm_sqr,
+        )
+    }
+}
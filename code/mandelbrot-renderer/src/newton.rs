@@ -0,0 +1,208 @@
+//! Newton fractals: every point of the complex plane is used as the starting point of Newton's method for a polynomial,
+//! and colored by the root it converges to and the number of steps it took.
+//!
+//! The polynomial is stored by its roots, because the Newton step `z - p(z) / p'(z)` simplifies to
+//! `z - 1 / sum(1 / (z - root))`, which the shader can evaluate without the coefficients.
+
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, bail, Context, Result};
+use bytemuck::{Pod, Zeroable};
+
+use crate::math::Complex;
+
+/// Starting points closer than this to a root count as converged
+pub const NEWTON_TOLERANCE: f64 = 1e-3;
+
+/// A polynomial of degree `1..=Polynomial::MAX_DEGREE` with a leading coefficient of 1, defined by its roots.
+/// Defaults to `z^3 - 1`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Polynomial {
+    roots: [(f64, f64); Polynomial::MAX_DEGREE],
+    degree: usize,
+}
+
+impl Default for Polynomial {
+    fn default() -> Self {
+        let half_sqrt_3 = 3.0_f64.sqrt() / 2.0;
+        Self::from_roots(&[(1.0, 0.0), (-0.5, half_sqrt_3), (-0.5, -half_sqrt_3)]).unwrap()
+    }
+}
+
+/// The root a starting point converged to, see [`Polynomial::converge`]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NewtonConvergence {
+    /// The index into [`Polynomial::roots`]
+    pub root: usize,
+    /// The (fractional) number of Newton steps until the point came closer than [`NEWTON_TOLERANCE`] to the root
+    pub iterations: f64,
+}
+
+impl Polynomial {
+    /// The highest degree the shader supports
+    pub const MAX_DEGREE: usize = 8;
+
+    pub fn from_roots(roots: &[(f64, f64)]) -> Result<Self> {
+        if roots.is_empty() || roots.len() > Self::MAX_DEGREE {
+            bail!("A polynomial needs between 1 and {} roots, not {}", Self::MAX_DEGREE, roots.len());
+        }
+        if let Some(root) = roots.iter().find(|root| !root.0.is_finite() || !root.1.is_finite()) {
+            bail!("The root {root:?} is not finite");
+        }
+
+        let mut polynomial = Self {
+            roots: [(0.0, 0.0); Self::MAX_DEGREE],
+            degree: roots.len(),
+        };
+        polynomial.roots[..roots.len()].copy_from_slice(roots);
+
+        Ok(polynomial)
+    }
+
+    /// Finds the roots of the polynomial `sum(coefficients[i] * z^i)` with the Durand-Kerner method.
+    /// Roots of multiplicity greater than one are only found with reduced precision.
+    pub fn from_coefficients(coefficients: &[(f64, f64)]) -> Result<Self> {
+        let coefficients: Vec<Complex> = coefficients.iter().copied().map(Complex::from).collect();
+        let Some(leading) = coefficients.iter().rposition(|coefficient| *coefficient != Complex::ZERO) else {
+            bail!("All coefficients are zero");
+        };
+        if leading == 0 {
+            bail!("A constant polynomial has no roots");
+        }
+
+        // Make the polynomial monic
+        let coefficients: Vec<Complex> = coefficients[..=leading]
+            .iter()
+            .map(|coefficient| *coefficient / coefficients[leading])
+            .collect();
+        let evaluate = |z: Complex| coefficients.iter().rev().fold(Complex::ZERO, |sum, coefficient| sum * z + *coefficient);
+
+        // Powers of a number that is neither real nor a root of unity spread the initial guesses out
+        let mut roots: Vec<Complex> = (0..leading as i32).map(|k| Complex(0.4, 0.9).powi(k)).collect();
+        for _ in 0..1000 {
+            let mut max_step: f64 = 0.0;
+            for k in 0..roots.len() {
+                let denominator = (0..roots.len())
+                    .filter(|j| *j != k)
+                    .fold(Complex::ONE, |product, j| product * (roots[k] - roots[j]));
+                let step = evaluate(roots[k]) / denominator;
+                if step.0.is_finite() && step.1.is_finite() {
+                    roots[k] = roots[k] - step;
+                    max_step = max_step.max(step.norm());
+                }
+            }
+
+            if max_step <= 1e-15 {
+                break;
+            }
+        }
+
+        let roots: Vec<(f64, f64)> = roots.into_iter().map(Into::into).collect();
+        Self::from_roots(&roots).context("Failed to find the roots of the polynomial")
+    }
+
+    pub fn roots(&self) -> &[(f64, f64)] {
+        &self.roots[..self.degree]
+    }
+
+    pub fn degree(&self) -> usize {
+        self.degree
+    }
+
+    /// The coefficients of `z^0` to `z^degree`, by expanding the product of `z - root`
+    pub fn coefficients(&self) -> Vec<(f64, f64)> {
+        let mut coefficients = vec![Complex::ONE];
+        for root in self.roots() {
+            // Multiply by (z - root)
+            let mut next = vec![Complex::ZERO; coefficients.len() + 1];
+            for (i, coefficient) in coefficients.iter().enumerate() {
+                next[i + 1] = next[i + 1] + *coefficient;
+                next[i] = next[i] - *coefficient * Complex::from(*root);
+            }
+            coefficients = next;
+        }
+
+        coefficients.into_iter().map(Into::into).collect()
+    }
+
+    /// Runs Newton's method from `start`, exactly like `newton` in `frag.wgsl`.
+    /// Returns `None` if it did not converge within `max_iterations` steps.
+    pub fn converge(&self, start: (f64, f64), max_iterations: u32) -> Option<NewtonConvergence> {
+        let mut z = Complex::from(start);
+
+        for i in 0..max_iterations {
+            let mut reciprocal_sum = Complex::ZERO;
+            for (root, position) in self.roots().iter().enumerate() {
+                let difference = z - Complex::from(*position);
+                let distance = difference.norm();
+                if distance < NEWTON_TOLERANCE {
+                    // Newton's method converges quadratically, so the logarithm of the distance doubles with every step
+                    let fraction = (distance.ln() / NEWTON_TOLERANCE.ln()).log2().clamp(0.0, 1.0);
+                    return Some(NewtonConvergence {
+                        root,
+                        iterations: i as f64 + 1.0 - fraction,
+                    });
+                }
+                reciprocal_sum = reciprocal_sum + Complex::ONE / difference;
+            }
+
+            z = z - Complex::ONE / reciprocal_sum;
+        }
+
+        None
+    }
+
+    pub(crate) fn to_uniform(self) -> PolynomialUniform {
+        let mut roots = [[0.0; 4]; Self::MAX_DEGREE];
+        for (uniform, root) in roots.iter_mut().zip(self.roots()) {
+            *uniform = [root.0 as f32, root.1 as f32, 0.0, 0.0];
+        }
+
+        PolynomialUniform {
+            roots,
+            degree: self.degree as u32,
+            _padding: [0; 3],
+        }
+    }
+}
+
+/// Formats the roots as `re,im;re,im;...`, e.g. for view descriptors
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, root) in self.roots().iter().enumerate() {
+            if i > 0 {
+                f.write_str(";")?;
+            }
+            write!(f, "{},{}", root.0, root.1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the format written by the [`fmt::Display`] implementation
+impl FromStr for Polynomial {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let roots = value
+            .split(';')
+            .map(|root| {
+                let (re, im) = root
+                    .split_once(',')
+                    .ok_or_else(|| anyhow!("The root `{root}` is not of the form `re,im`"))?;
+                Ok((re.trim().parse()?, im.trim().parse()?))
+            })
+            .collect::<Result<Vec<(f64, f64)>>>()?;
+
+        Self::from_roots(&roots)
+    }
+}
+
+/// The layout of `Polynomial` in `frag.wgsl`. Array elements of uniforms have to be aligned to 16 bytes.
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+pub(crate) struct PolynomialUniform {
+    roots: [[f32; 4]; Polynomial::MAX_DEGREE],
+    degree: u32,
+    _padding: [u32; 3],
+}
@@ -0,0 +1,276 @@
+//! An image drawn over every frame, e.g. a heads-up display. The renderer has no text rendering of its own, so the
+//! image is rasterized by the application.
+
+/// An image drawn over the fractal, see [`MandelbrotRenderer::set_overlay`](crate::MandelbrotRenderer::set_overlay)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Overlay {
+    pub width: u32,
+    pub height: u32,
+    /// 8-bit sRGB RGBA pixels with straight alpha, row by row from the top left
+    pub pixels: Vec<u8>,
+    /// The offset of the top left corner from the top left of the window in pixels
+    pub position: (u32, u32),
+}
+
+impl Overlay {
+    /// A fully transparent image
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width as usize * height as usize * 4],
+            position: (0, 0),
+        }
+    }
+
+    pub fn with_position(mut self, position: (u32, u32)) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Sets a pixel, ignoring coordinates outside of the image
+    pub fn set_pixel(&mut self, (x, y): (u32, u32), rgba: [u8; 4]) {
+        if x < self.width && y < self.height {
+            let index = (y as usize * self.width as usize + x as usize) * 4;
+            self.pixels[index..index + 4].copy_from_slice(&rgba);
+        }
+    }
+}
+
+pub(crate) struct OverlayRenderer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    rect_buffer: wgpu::Buffer,
+    /// Matches how the render target stores colors, so blending happens in the same space as the fractal
+    format: wgpu::TextureFormat,
+    /// The uploaded image and the bind group to sample it with, `None` while no overlay is shown
+    target: Option<OverlayTarget>,
+}
+
+struct OverlayTarget {
+    size: (u32, u32),
+    position: (u32, u32),
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+}
+
+impl OverlayRenderer {
+    /// `render_format` is the format of the render target. If `encode_srgb` is set, the render target is linear but holds
+    /// sRGB values, so the overlay is copied over without decoding it.
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        render_format: wgpu::TextureFormat,
+        encode_srgb: bool,
+        vertex_layout: wgpu::VertexBufferLayout,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Overlay Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/overlay.wgsl"));
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Overlay Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vertex_main",
+                buffers: &[vertex_layout],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fragment_main",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: render_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::all(),
+                })],
+            }),
+            multiview: None,
+        });
+
+        // Overlays are drawn pixel for pixel
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Overlay Sampler"),
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let rect_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Overlay Rect Buffer"),
+            size: std::mem::size_of::<[f32; 4]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+            rect_buffer,
+            format: if encode_srgb {
+                wgpu::TextureFormat::Rgba8Unorm
+            } else {
+                wgpu::TextureFormat::Rgba8UnormSrgb
+            },
+            target: None,
+        }
+    }
+
+    /// Uploads the image of `overlay`, or hides the overlay if it is `None`
+    pub(crate) fn set_overlay(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, overlay: Option<&Overlay>) {
+        let Some(overlay) = overlay.filter(|overlay| overlay.width > 0 && overlay.height > 0) else {
+            self.target = None;
+            return;
+        };
+
+        let size = (overlay.width, overlay.height);
+        let extent = wgpu::Extent3d {
+            width: overlay.width,
+            height: overlay.height,
+            depth_or_array_layers: 1,
+        };
+        if self.target.as_ref().map(|target| target.size) != Some(size) {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Overlay Texture"),
+                size: extent,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: self.format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Overlay Bind Group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: self.rect_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+
+            self.target = Some(OverlayTarget {
+                size,
+                position: overlay.position,
+                texture,
+                bind_group,
+            });
+        }
+
+        let target = self.target.as_mut().unwrap();
+        target.position = overlay.position;
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &target.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &overlay.pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(overlay.width * 4),
+                rows_per_image: None,
+            },
+            extent,
+        );
+    }
+
+    /// Records a render pass that draws the overlay over `view`, a render target of `window_size` pixels, with the
+    /// vertices of a full-screen quad
+    pub(crate) fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        view: &wgpu::TextureView,
+        window_size: (u32, u32),
+        vertex_buffer: &wgpu::Buffer,
+        vertex_count: u32,
+    ) {
+        let Some(target) = &self.target else {
+            return;
+        };
+
+        // Pixel coordinates from the top left to clip space
+        let to_clip = |(x, y): (u32, u32)| {
+            [
+                x as f32 / window_size.0.max(1) as f32 * 2.0 - 1.0,
+                1.0 - y as f32 / window_size.1.max(1) as f32 * 2.0,
+            ]
+        };
+        let [left, top] = to_clip(target.position);
+        let [right, bottom] = to_clip((target.position.0 + target.size.0, target.position.1 + target.size.1));
+        queue.write_buffer(&self.rect_buffer, 0, bytemuck::cast_slice(&[left, top, right, bottom]));
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Overlay Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &target.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.draw(0..vertex_count, 0..1);
+    }
+}
@@ -0,0 +1,568 @@
+//! Color palettes, and parsers for the gradient files of other programs.
+//!
+//! A palette is a cycle of colors with one entry per iteration, and smooth shading blends between neighbouring entries.
+//! Fractint `.map` files are used entry by entry, while the continuous gradients of GIMP `.ggr` and Ultra Fractal
+//! `.ugr` files are sampled into a given number of entries, which is how many iterations one cycle takes.
+//!
+//! Colors in gradient files are sRGB and are converted into linear light, so they come out as in the program they were
+//! made with. The built-in palette predates this and is used as linear light directly.
+//!
+//! How neighbouring entries are blended is chosen with [`PaletteInterpolation`]. Blending linear RGB is the cheapest,
+//! but its midpoints between saturated colors are darker and greyer than either end.
+
+use std::f64::consts::PI;
+
+use anyhow::{anyhow, bail, Context, Result};
+use bytemuck::{Pod, Zeroable};
+
+use crate::color::{
+    hsluv_to_linear_srgb, lab_to_lch, lch_to_lab, linear_srgb_to_hsluv, linear_srgb_to_oklab, oklab_to_linear_srgb,
+    srgb_to_linear,
+};
+
+/// The name of [`Palette::default`]
+pub const DEFAULT_PALETTE_NAME: &str = "default";
+
+/// A named cycle of linear colors, see the [module documentation](self)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    name: String,
+    colors: Vec<[f32; 3]>,
+    interpolation: PaletteInterpolation,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        let colors = DEFAULT_PALETTE.map(|color| color.map(|channel| channel as f32 / 255.0));
+        Self::new(DEFAULT_PALETTE_NAME, colors.to_vec()).unwrap()
+    }
+}
+
+impl Palette {
+    /// The most entries the shader supports, as many as a Fractint map has
+    pub const MAX_COLORS: usize = 256;
+    /// How many entries gradients are sampled into by [`Palette::load`]
+    pub const DEFAULT_GRADIENT_LENGTH: usize = 64;
+
+    /// Creates a palette from linear colors
+    pub fn new(name: impl Into<String>, colors: Vec<[f32; 3]>) -> Result<Self> {
+        if colors.is_empty() || colors.len() > Self::MAX_COLORS {
+            bail!("A palette needs between 1 and {} colors, not {}", Self::MAX_COLORS, colors.len());
+        }
+
+        Ok(Self {
+            name: name.into(),
+            colors,
+            interpolation: PaletteInterpolation::default(),
+        })
+    }
+
+    /// The color space neighbouring entries are blended in
+    pub fn with_interpolation(mut self, interpolation: PaletteInterpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    /// Creates a palette from 8-bit sRGB colors
+    pub fn from_srgb(name: impl Into<String>, colors: &[[u8; 3]]) -> Result<Self> {
+        Self::new(name, colors.iter().map(|color| color.map(srgb_to_linear)).collect())
+    }
+
+    /// Shown in view descriptors. Only the built-in palette can be restored from its name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn colors(&self) -> &[[f32; 3]] {
+        &self.colors
+    }
+
+    pub fn interpolation(&self) -> PaletteInterpolation {
+        self.interpolation
+    }
+
+    /// The entry for a whole number of iterations, wrapping around at the end of the palette
+    pub fn entry(&self, index: usize) -> [f32; 3] {
+        self.colors[index % self.colors.len()]
+    }
+
+    /// The linear color for a (fractional) number of iterations, blending between neighbouring entries like `frag.wgsl`
+    pub fn color(&self, iterations: f64) -> [f64; 3] {
+        let whole_iterations = iterations.floor() as usize;
+        let (color1, color2) = (self.entry(whole_iterations), self.entry(whole_iterations + 1));
+        let fract = iterations % 1.0;
+
+        self.interpolation.mix(color1.map(f64::from), color2.map(f64::from), fract)
+    }
+
+    /// Parses a GIMP gradient (`.ggr`) and samples it into `length` entries.
+    /// Transparency and the foreground and background color types of segments are ignored.
+    pub fn parse_ggr(source: &str, length: usize) -> Result<Self> {
+        let mut lines = source.lines().map(str::trim).filter(|line| !line.is_empty());
+        if lines.next() != Some("GIMP Gradient") {
+            bail!("Not a GIMP gradient, the file has to start with `GIMP Gradient`");
+        }
+
+        // Older gradients have no name
+        let mut line = lines.next().ok_or_else(|| anyhow!("The gradient ends before its segments"))?;
+        let mut name = "";
+        if let Some(value) = line.strip_prefix("Name:") {
+            name = value.trim();
+            line = lines.next().ok_or_else(|| anyhow!("The gradient ends before its segments"))?;
+        }
+
+        let segment_count: usize = line
+            .parse()
+            .with_context(|| format!("Expected the number of segments, not `{line}`"))?;
+        let segments = lines
+            .take(segment_count)
+            .map(GradientSegment::parse)
+            .collect::<Result<Vec<_>>>()?;
+        if segments.len() != segment_count {
+            bail!("The gradient has {} of {segment_count} segments", segments.len());
+        }
+
+        let colors: Vec<[u8; 3]> = (0..length)
+            .map(|i| {
+                let position = i as f64 / (length - 1).max(1) as f64;
+                let segment = segments
+                    .iter()
+                    .find(|segment| position <= segment.right)
+                    .or(segments.last())
+                    .ok_or_else(|| anyhow!("The gradient has no segments"))?;
+                Ok(segment.color(position).map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8))
+            })
+            .collect::<Result<_>>()?;
+
+        Self::from_srgb(name, &colors)
+    }
+
+    /// Parses a Fractint map (`.map`) with one `red green blue` entry per line, each from 0 to 255.
+    /// Anything after the third number of a line is a comment.
+    pub fn parse_map(name: impl Into<String>, source: &str) -> Result<Self> {
+        let colors = source
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(line_number, line)| {
+                let mut channels = line.split_whitespace().map(str::parse::<u8>);
+                let mut channel = || channels.next().and_then(Result::ok);
+                match (channel(), channel(), channel()) {
+                    (Some(r), Some(g), Some(b)) => Ok([r, g, b]),
+                    _ => bail!("Invalid color on line {}: `{line}`", line_number + 1),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Self::from_srgb(name, &colors)
+    }
+
+    /// Parses the gradients of an Ultra Fractal gradient file (`.ugr`) and samples each into `length` entries.
+    /// Gradients are interpolated linearly, even if they are marked as smooth, and rotation and opacity are ignored.
+    pub fn parse_ugr(source: &str, length: usize) -> Result<Vec<Self>> {
+        let mut palettes = Vec::new();
+
+        let mut rest = source;
+        while let Some(open) = rest.find('{') {
+            // Anything before the name, e.g. comments or the end of the previous entry
+            let block_name = rest[..open].trim().lines().last().unwrap_or_default().trim();
+            let close = rest[open..]
+                .find('}')
+                .ok_or_else(|| anyhow!("The entry `{block_name}` is not closed"))?
+                + open;
+            let block = &rest[open + 1..close];
+            rest = &rest[close + 1..];
+
+            let (title, points) =
+                parse_ugr_block(block).with_context(|| format!("Invalid gradient `{block_name}`"))?;
+            let colors: Vec<[u8; 3]> = (0..length)
+                .map(|i| ugr_color(&points, (i * UGR_POSITIONS) as f64 / length as f64))
+                .collect();
+            palettes.push(Self::from_srgb(title.unwrap_or(block_name), &colors)?);
+        }
+
+        if palettes.is_empty() {
+            bail!("The file contains no gradients");
+        }
+
+        Ok(palettes)
+    }
+
+    /// Loads a `.ggr`, `.map` or `.ugr` file, depending on its extension. Of Ultra Fractal files, the first gradient is used.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+        let name = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+
+        let palette = match extension.to_ascii_lowercase().as_str() {
+            "ggr" => Self::parse_ggr(&source, Self::DEFAULT_GRADIENT_LENGTH),
+            "map" => Self::parse_map(name, &source),
+            "ugr" => Self::parse_ugr(&source, Self::DEFAULT_GRADIENT_LENGTH).map(|palettes| palettes[0].clone()),
+            _ => bail!("Unknown gradient file extension `{extension}`, expected `ggr`, `map` or `ugr`"),
+        };
+        palette.with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub(crate) fn to_uniform(&self) -> PaletteUniform {
+        let mut colors = [[0.0; 4]; Self::MAX_COLORS];
+        for (uniform, color) in colors.iter_mut().zip(&self.colors) {
+            *uniform = [color[0], color[1], color[2], 0.0];
+        }
+
+        PaletteUniform {
+            colors,
+            length: self.colors.len() as u32,
+            interpolation: self.interpolation.shader_value(),
+            _padding: [0; 2],
+        }
+    }
+}
+
+/// The color space in which neighbouring palette entries are blended
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PaletteInterpolation {
+    /// Blends the channels of linear sRGB, how palettes were always blended
+    #[default]
+    LinearRgb,
+    /// Blends in OKLab, where perceived lightness changes evenly
+    Oklab,
+    /// Blends the lightness, chroma and hue of OKLab, going around the shorter way of the hue circle.
+    /// Keeps midpoints as colorful as the ends.
+    Oklch,
+    /// Blends the hue, saturation and lightness of HSLuv, going around the shorter way of the hue circle.
+    /// Saturation is relative to the most colorful sRGB color, so midpoints stay inside the gamut.
+    Hsluv,
+}
+
+impl PaletteInterpolation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PaletteInterpolation::LinearRgb => "linear-rgb",
+            PaletteInterpolation::Oklab => "oklab",
+            PaletteInterpolation::Oklch => "oklch",
+            PaletteInterpolation::Hsluv => "hsluv",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear-rgb" => Some(PaletteInterpolation::LinearRgb),
+            "oklab" => Some(PaletteInterpolation::Oklab),
+            "oklch" => Some(PaletteInterpolation::Oklch),
+            "hsluv" => Some(PaletteInterpolation::Hsluv),
+            _ => None,
+        }
+    }
+
+    /// The value of `Palette::interpolation` in `frag.wgsl`
+    fn shader_value(&self) -> u32 {
+        match self {
+            PaletteInterpolation::LinearRgb => 0,
+            PaletteInterpolation::Oklab => 1,
+            PaletteInterpolation::Oklch => 2,
+            PaletteInterpolation::Hsluv => 3,
+        }
+    }
+
+    /// Blends two linear colors like `mix_colors` in `frag.wgsl`, clamped to the sRGB gamut
+    pub fn mix(&self, color1: [f64; 3], color2: [f64; 3], t: f64) -> [f64; 3] {
+        let color = match self {
+            PaletteInterpolation::LinearRgb => mix(color1, color2, t),
+            PaletteInterpolation::Oklab => {
+                oklab_to_linear_srgb(mix(linear_srgb_to_oklab(color1), linear_srgb_to_oklab(color2), t))
+            }
+            PaletteInterpolation::Oklch => {
+                let (lch1, lch2) = (
+                    lab_to_lch(linear_srgb_to_oklab(color1)),
+                    lab_to_lch(linear_srgb_to_oklab(color2)),
+                );
+                oklab_to_linear_srgb(lch_to_lab(mix_polar(lch1, lch2, t, OKLCH_ACHROMATIC)))
+            }
+            PaletteInterpolation::Hsluv => {
+                let (hsluv1, hsluv2) = (linear_srgb_to_hsluv(color1), linear_srgb_to_hsluv(color2));
+                hsluv_to_linear_srgb(mix_polar(hsluv1, hsluv2, t, HSLUV_ACHROMATIC))
+            }
+        };
+
+        color.map(|channel| channel.clamp(0.0, 1.0))
+    }
+}
+
+/// Below this OKLCh chroma, the hue of a color is meaningless
+const OKLCH_ACHROMATIC: f64 = 1e-4;
+/// Below this HSLuv saturation (out of 100), the hue of a color is meaningless
+const HSLUV_ACHROMATIC: f64 = 1e-2;
+
+fn mix(color1: [f64; 3], color2: [f64; 3], t: f64) -> [f64; 3] {
+    [0, 1, 2].map(|channel| color1[channel] * (1.0 - t) + color2[channel] * t)
+}
+
+/// Blends (lightness, chroma, hue) coordinates, with the hue in radians.
+/// Colors without chroma take the hue of the other color, so that blending towards grey does not pass other hues.
+fn mix_polar(mut color1: [f64; 3], mut color2: [f64; 3], t: f64, achromatic: f64) -> [f64; 3] {
+    if color1[1] < achromatic {
+        color1[2] = color2[2];
+    } else if color2[1] < achromatic {
+        color2[2] = color1[2];
+    }
+
+    // The shorter way around the hue circle
+    let hue_distance = (color2[2] - color1[2] + PI).rem_euclid(2.0 * PI) - PI;
+    [
+        color1[0] * (1.0 - t) + color2[0] * t,
+        color1[1] * (1.0 - t) + color2[1] * t,
+        color1[2] + hue_distance * t,
+    ]
+}
+
+/// A segment of a GIMP gradient, with positions from 0 to 1 and sRGB colors
+struct GradientSegment {
+    left: f64,
+    middle: f64,
+    right: f64,
+    left_color: [f64; 3],
+    right_color: [f64; 3],
+    blending: u32,
+    coloring: u32,
+}
+
+impl GradientSegment {
+    /// Parses `left middle right r g b a r g b a blending coloring [left_type right_type]`
+    fn parse(line: &str) -> Result<Self> {
+        let values = line
+            .split_whitespace()
+            .map(str::parse::<f64>)
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("Invalid segment `{line}`"))?;
+        if values.len() < 13 {
+            bail!("Invalid segment `{line}`, expected at least 13 numbers");
+        }
+
+        Ok(Self {
+            left: values[0],
+            middle: values[1],
+            right: values[2],
+            left_color: [values[3], values[4], values[5]],
+            right_color: [values[7], values[8], values[9]],
+            blending: values[11] as u32,
+            coloring: values[12] as u32,
+        })
+    }
+
+    /// The color at a position of the whole gradient, like `gimp_gradient_get_color_at`
+    fn color(&self, position: f64) -> [f64; 3] {
+        let length = self.right - self.left;
+        let (middle, position) = if length < f64::EPSILON {
+            (0.5, 0.5)
+        } else {
+            ((self.middle - self.left) / length, ((position - self.left) / length).clamp(0.0, 1.0))
+        };
+
+        let linear = || {
+            if position <= middle {
+                if middle < f64::EPSILON {
+                    0.0
+                } else {
+                    0.5 * position / middle
+                }
+            } else if middle > 1.0 - f64::EPSILON {
+                1.0
+            } else {
+                0.5 + 0.5 * (position - middle) / (1.0 - middle)
+            }
+        };
+        let factor = match self.blending {
+            1 => position.powf(0.5_f64.ln() / middle.max(f64::EPSILON).ln()),
+            2 => ((-PI / 2.0 + PI * linear()).sin() + 1.0) / 2.0,
+            3 => (1.0 - (linear() - 1.0).powi(2)).sqrt(),
+            4 => 1.0 - (1.0 - linear().powi(2)).sqrt(),
+            5 => (position >= middle) as u32 as f64,
+            _ => linear(),
+        };
+
+        match self.coloring {
+            // Hue counter-clockwise and clockwise
+            coloring @ (1 | 2) => {
+                let (left, right) = (rgb_to_hsv(self.left_color), rgb_to_hsv(self.right_color));
+                let mut hue_distance = right[0] - left[0];
+                if coloring == 1 && hue_distance < 0.0 {
+                    hue_distance += 1.0;
+                } else if coloring == 2 && hue_distance > 0.0 {
+                    hue_distance -= 1.0;
+                }
+
+                let hue = (left[0] + hue_distance * factor).rem_euclid(1.0);
+                let saturation = left[1] + (right[1] - left[1]) * factor;
+                let value = left[2] + (right[2] - left[2]) * factor;
+                hsv_to_rgb([hue, saturation, value])
+            }
+            _ => [0, 1, 2].map(|channel| {
+                self.left_color[channel] + (self.right_color[channel] - self.left_color[channel]) * factor
+            }),
+        }
+    }
+}
+
+/// Hue, saturation and value from 0 to 1
+fn rgb_to_hsv([r, g, b]: [f64; 3]) -> [f64; 3] {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        ((g - b) / delta).rem_euclid(6.0) / 6.0
+    } else if max == g {
+        ((b - r) / delta + 2.0) / 6.0
+    } else {
+        ((r - g) / delta + 4.0) / 6.0
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+
+    [hue, saturation, max]
+}
+
+fn hsv_to_rgb([hue, saturation, value]: [f64; 3]) -> [f64; 3] {
+    let sector = hue * 6.0;
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let min = value - chroma;
+
+    let [r, g, b] = match sector as u32 {
+        0 => [chroma, x, 0.0],
+        1 => [x, chroma, 0.0],
+        2 => [0.0, chroma, x],
+        3 => [0.0, x, chroma],
+        4 => [x, 0.0, chroma],
+        _ => [chroma, 0.0, x],
+    };
+    [r + min, g + min, b + min]
+}
+
+/// The number of positions of an Ultra Fractal gradient, control points have an index from 0 to 399
+const UGR_POSITIONS: usize = 400;
+
+/// A control point of an Ultra Fractal gradient, its index and sRGB color
+type UgrPoint = (usize, [u8; 3]);
+
+/// Parses the title and control points (sorted by index) of a `.ugr` entry
+fn parse_ugr_block(block: &str) -> Result<(Option<&str>, Vec<UgrPoint>)> {
+    let mut title = None;
+    let mut points = Vec::new();
+    let mut index = None;
+
+    let mut in_gradient = false;
+    for line in block.lines().map(str::trim) {
+        if let Some(section) = line.strip_suffix(':') {
+            in_gradient = section == "gradient";
+            continue;
+        }
+        if !in_gradient {
+            continue;
+        }
+
+        for (key, value) in ugr_key_values(line) {
+            match key {
+                "title" => title = Some(value),
+                "index" => {
+                    let value: i64 = value.parse().with_context(|| format!("Invalid index `{value}`"))?;
+                    index = Some(value.rem_euclid(UGR_POSITIONS as i64) as usize);
+                }
+                "color" => {
+                    let index = index.take().ok_or_else(|| anyhow!("The color `{value}` has no index"))?;
+                    // Windows COLORREF, 0x00bbggrr
+                    let color: u32 = value.parse().with_context(|| format!("Invalid color `{value}`"))?;
+                    points.push((index, [color as u8, (color >> 8) as u8, (color >> 16) as u8]));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if points.is_empty() {
+        bail!("The gradient has no colors");
+    }
+    points.sort_by_key(|(index, _)| *index);
+
+    Ok((title, points))
+}
+
+/// Splits a line into `key=value` pairs, where values may be quoted and contain spaces
+fn ugr_key_values(line: &str) -> Vec<(&str, &str)> {
+    let mut key_values = Vec::new();
+
+    let mut rest = line.trim_start();
+    while let Some(equals) = rest.find('=') {
+        let key = rest[..equals].trim();
+        let value_start = &rest[equals + 1..];
+        let (value, next) = match value_start.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"').unwrap_or(quoted.len());
+                (&quoted[..end], quoted.get(end + 1..).unwrap_or_default())
+            }
+            None => {
+                let end = value_start.find(char::is_whitespace).unwrap_or(value_start.len());
+                (&value_start[..end], &value_start[end..])
+            }
+        };
+
+        key_values.push((key, value));
+        rest = next.trim_start();
+    }
+
+    key_values
+}
+
+/// The color at a position from 0 to 400, interpolating cyclically between the control points around it
+fn ugr_color(points: &[UgrPoint], position: f64) -> [u8; 3] {
+    let next = points.iter().position(|(index, _)| *index as f64 > position).unwrap_or(0);
+    let previous = (next + points.len() - 1) % points.len();
+    let ((start, start_color), (end, end_color)) = (points[previous], points[next]);
+
+    let mut span = end as f64 - start as f64;
+    let mut offset = position - start as f64;
+    if span <= 0.0 {
+        span += UGR_POSITIONS as f64;
+    }
+    if offset < 0.0 {
+        offset += UGR_POSITIONS as f64;
+    }
+
+    let t = offset / span;
+    [0, 1, 2].map(|channel| {
+        (start_color[channel] as f64 + (end_color[channel] as f64 - start_color[channel] as f64) * t).round() as u8
+    })
+}
+
+/// The built-in palette
+const DEFAULT_PALETTE: [[u8; 3]; 16] = [
+    [66, 30, 15],
+    [25, 7, 26],
+    [9, 1, 47],
+    [4, 4, 73],
+    [0, 7, 100],
+    [12, 44, 138],
+    [24, 82, 177],
+    [57, 125, 209],
+    [134, 181, 229],
+    [211, 236, 248],
+    [241, 233, 191],
+    [248, 201, 95],
+    [255, 170, 0],
+    [204, 128, 0],
+    [153, 87, 0],
+    [106, 52, 3],
+];
+
+/// The layout of `Palette` in `frag.wgsl`. Array elements of uniforms have to be aligned to 16 bytes.
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+pub(crate) struct PaletteUniform {
+    colors: [[f32; 4]; Palette::MAX_COLORS],
+    length: u32,
+    interpolation: u32,
+    _padding: [u32; 2],
+}
@@ -0,0 +1,320 @@
+//! Dynamic resolution scaling, which keeps interaction responsive on slow GPUs.
+//!
+//! Frames that are rendered at a lower resolution are drawn into a texture first, and stretched onto the surface with
+//! bilinear filtering. This skips multisampling, which a blurred frame does not need.
+
+use web_time::{Duration, Instant};
+
+/// How [`crate::MandelbrotRenderer`] trades resolution for responsiveness while the view changes.
+///
+/// During interaction (see [`crate::MandelbrotRenderer::mark_interaction`]), and while the camera moves slower than
+/// the target frame time allows, frames are rendered at a fraction of the surface resolution and upscaled.
+/// Once the input stops and the camera stands still, frames are rendered at full resolution again.
+///
+/// The default policy always renders at full resolution.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct QualityPolicy {
+    interaction_scale: f32,
+    target_frame_time: Option<Duration>,
+    min_scale: f32,
+    settle_delay: Duration,
+}
+
+impl Default for QualityPolicy {
+    fn default() -> Self {
+        Self {
+            interaction_scale: 1.0,
+            target_frame_time: None,
+            min_scale: 0.25,
+            settle_delay: Duration::from_millis(150),
+        }
+    }
+}
+
+impl QualityPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The resolution scale (from 0 to 1, per axis) of frames rendered during interaction
+    pub fn with_interaction_scale(mut self, interaction_scale: f32) -> Self {
+        self.interaction_scale = interaction_scale.clamp(f32::EPSILON, 1.0);
+        self
+    }
+
+    /// Lowers the resolution of moving frames further whenever the time between frames exceeds this.
+    /// Has to be longer than the refresh interval of the display, as frames are never presented faster than that.
+    pub fn with_target_frame_time(mut self, target_frame_time: Duration) -> Self {
+        self.target_frame_time = Some(target_frame_time);
+        self
+    }
+
+    /// The lowest resolution scale the target frame time can lead to
+    pub fn with_min_scale(mut self, min_scale: f32) -> Self {
+        self.min_scale = min_scale.clamp(f32::EPSILON, 1.0);
+        self
+    }
+
+    /// How long after the last interaction the full resolution is restored
+    pub fn with_settle_delay(mut self, settle_delay: Duration) -> Self {
+        self.settle_delay = settle_delay;
+        self
+    }
+
+    pub fn interaction_scale(&self) -> f32 {
+        self.interaction_scale
+    }
+
+    pub fn target_frame_time(&self) -> Option<Duration> {
+        self.target_frame_time
+    }
+
+    pub fn min_scale(&self) -> f32 {
+        self.min_scale
+    }
+
+    pub fn settle_delay(&self) -> Duration {
+        self.settle_delay
+    }
+}
+
+/// How much of a new frame time goes into the moving average
+const FRAME_TIME_SMOOTHING: f32 = 0.2;
+
+/// Chooses the resolution scale of each frame according to a [`QualityPolicy`]
+#[derive(Debug)]
+pub(crate) struct QualityController {
+    last_interaction: Option<Instant>,
+    /// When the previous frame was rendered, and whether the view was changing then
+    last_frame: Option<(Instant, bool)>,
+    /// Moving average of the time between changing frames, in seconds
+    average_frame_time: Option<f32>,
+    /// The scale the target frame time allows for
+    adaptive_scale: f32,
+}
+
+impl QualityController {
+    pub(crate) fn new() -> Self {
+        Self {
+            last_interaction: None,
+            last_frame: None,
+            average_frame_time: None,
+            adaptive_scale: 1.0,
+        }
+    }
+
+    pub(crate) fn mark_interaction(&mut self) {
+        self.last_interaction = Some(Instant::now());
+    }
+
+    /// The resolution scale of the next frame, from 0 to 1
+    pub(crate) fn frame_scale(&mut self, policy: &QualityPolicy, camera_moved: bool) -> f32 {
+        let now = Instant::now();
+        let interacting = self
+            .last_interaction
+            .is_some_and(|last_interaction| now.duration_since(last_interaction) < policy.settle_delay);
+        let changing = interacting || camera_moved;
+
+        // Only the time between two changing frames says something about how fast frames can be rendered
+        if let Some((last_frame, true)) = self.last_frame {
+            if changing {
+                self.update_adaptive_scale(policy, now.duration_since(last_frame));
+            }
+        }
+        self.last_frame = Some((now, changing));
+
+        match (changing, interacting) {
+            (false, _) => 1.0,
+            (true, false) => self.adaptive_scale,
+            (true, true) => self.adaptive_scale.min(policy.interaction_scale),
+        }
+    }
+
+    fn update_adaptive_scale(&mut self, policy: &QualityPolicy, frame_time: Duration) {
+        let Some(target_frame_time) = policy.target_frame_time else {
+            self.adaptive_scale = 1.0;
+            return;
+        };
+
+        let frame_time = frame_time.as_secs_f32();
+        let average_frame_time = match self.average_frame_time {
+            Some(average) => average + (frame_time - average) * FRAME_TIME_SMOOTHING,
+            None => frame_time,
+        };
+        self.average_frame_time = Some(average_frame_time);
+
+        // The number of pixels grows with the square of the scale. Changes are limited to avoid oscillating.
+        let correction = (target_frame_time.as_secs_f32() / average_frame_time).sqrt().clamp(0.9, 1.05);
+        self.adaptive_scale = (self.adaptive_scale * correction).clamp(policy.min_scale, 1.0);
+    }
+}
+
+/// Renders frames into a texture of a lower resolution, and stretches that texture over the render target
+pub(crate) struct Upscaler {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    format: wgpu::TextureFormat,
+    /// The texture frames are rendered into and the bind group to sample it with, recreated whenever the size changes
+    target: Option<UpscaleTarget>,
+}
+
+struct UpscaleTarget {
+    size: (u32, u32),
+    view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Upscaler {
+    /// `format` is used both for the low-resolution texture and the render target
+    pub(crate) fn new(device: &wgpu::Device, format: wgpu::TextureFormat, vertex_layout: wgpu::VertexBufferLayout) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Upscale Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/upscale.wgsl"));
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Upscale Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vertex_main",
+                buffers: &[vertex_layout],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fragment_main",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::all(),
+                })],
+            }),
+            multiview: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Upscale Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+            format,
+            target: None,
+        }
+    }
+
+    /// The texture to render a frame of the given size into
+    pub(crate) fn target_view(&mut self, device: &wgpu::Device, size: (u32, u32)) -> &wgpu::TextureView {
+        if self.target.as_ref().map(|target| target.size) != Some(size) {
+            let view = device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some("Upscale Texture"),
+                    size: wgpu::Extent3d {
+                        width: size.0,
+                        height: size.1,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: self.format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default());
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Upscale Bind Group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            });
+
+            self.target = Some(UpscaleTarget { size, view, bind_group });
+        }
+
+        &self.target.as_ref().unwrap().view
+    }
+
+    /// Records a render pass that stretches the most recent target texture over `view`, with the vertices of a full-screen quad
+    pub(crate) fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        vertex_buffer: &wgpu::Buffer,
+        vertex_count: u32,
+    ) {
+        let Some(target) = &self.target else {
+            return;
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Upscale Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &target.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.draw(0..vertex_count, 0..1);
+    }
+}
+
+/// The size of a frame rendered at the given resolution scale, at least one pixel in each direction
+pub(crate) fn scaled_size((width, height): (u32, u32), scale: f32) -> (u32, u32) {
+    (
+        ((width as f32 * scale).round() as u32).min(width).max(1),
+        ((height as f32 * scale).round() as u32).min(height).max(1),
+    )
+}
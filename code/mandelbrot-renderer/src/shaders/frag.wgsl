@@ -1,6 +1,38 @@
-const MAX_ITERATIONS: u32 = 400;
-const SMOOTH_SHADING: bool = true;
-const CUSTOM_ADDITIONAL_COLORS: bool = true;
+// These are set by the renderer, see `ShaderConstants`
+// See `Fractal`
+override FRACTAL: u32 = 0u;
+override MAX_ITERATIONS: u32 = 400u;
+override SMOOTH_SHADING: bool = true;
+override CUSTOM_ADDITIONAL_COLORS: bool = true;
+// See `Coloring`
+override COLORING: u32 = 0u;
+override COMBINE_ESCAPE_TIME: bool = true;
+// Set if the render target is linear instead of sRGB
+override ENCODE_SRGB: bool = false;
+
+const FRACTAL_MANDELBROT: u32 = 0u;
+const FRACTAL_NEWTON: u32 = 1u;
+const FRACTAL_CUSTOM: u32 = 2u;
+
+const COLORING_ESCAPE_TIME: u32 = 0u;
+const COLORING_BINARY_DECOMPOSITION: u32 = 1u;
+const COLORING_FIELD_LINES: u32 = 2u;
+
+const INTERPOLATION_LINEAR_RGB: u32 = 0u;
+const INTERPOLATION_OKLAB: u32 = 1u;
+const INTERPOLATION_OKLCH: u32 = 2u;
+const INTERPOLATION_HSLUV: u32 = 3u;
+
+const PI: f32 = 3.14159265358979;
+// The width of field lines, as a fraction of an iteration band
+const FIELD_LINE_WIDTH: f32 = 0.04;
+
+// See `newton.rs`
+const NEWTON_TOLERANCE: f32 = 0.001;
+// Where in the palette the color of the first root is. The other roots are spread out evenly from there.
+const NEWTON_PALETTE_OFFSET: f32 = 0.375;
+// How much darker a point gets with every step it takes to converge
+const NEWTON_SHADING: f32 = 0.9;
 
 struct Camera {
     position: vec2<f32>,
@@ -11,6 +43,30 @@ struct Camera {
 @binding(0)
 var<uniform> camera: Camera;
 
+// The roots of the polynomial of the Newton fractal
+struct Polynomial {
+    // Only xy is used, as array elements of uniforms are aligned to 16 bytes
+    roots: array<vec4<f32>, 8>,
+    degree: u32,
+}
+
+@group(0)
+@binding(1)
+var<uniform> polynomial: Polynomial;
+
+// See `Palette`
+struct Palette {
+    // Linear colors, only rgb is used
+    colors: array<vec4<f32>, 256>,
+    length: u32,
+    // See `PaletteInterpolation`
+    interpolation: u32,
+}
+
+@group(0)
+@binding(2)
+var<uniform> palette: Palette;
+
 @group(1)
 @binding(0)
 var<uniform> time: u32;
@@ -19,17 +75,32 @@ struct VertexInput {
     @location(0) coord: vec2<f32>,
 };
 
+struct Escape {
+    // The (fractional) number of iterations until the orbit escaped
+    iterations: f32,
+    // The estimated distance to the set on the complex plane, 0 inside the set
+    distance: f32,
+    // The value of z once the orbit escaped, 0 inside the set
+    z: vec2<f32>,
+}
+
+struct NewtonConvergence {
+    // The index of the root, or the degree if the point did not converge
+    root: u32,
+    // The (fractional) number of steps until the point came close to the root
+    iterations: f32,
+}
+
 @fragment
 fn main(vertex: VertexInput) -> @location(0) vec4<f32> {
-    var position = vertex.coord;
+    let position = camera_position(vertex);
 
-    // Camera scale
-    position *= camera.size;
-
-    // Camera offset
-    position += camera.position;
+    if (FRACTAL == FRACTAL_NEWTON) {
+        return output_color(newton_color(position));
+    }
 
-    var iterations = mandelbrot(vec2<f32>(position), MAX_ITERATIONS);
+    let escape = escape_time(position);
+    var iterations = escape.iterations;
 
     var color: vec3<f32>;
     if iterations % 2 == 0 {
@@ -45,7 +116,7 @@ fn main(vertex: VertexInput) -> @location(0) vec4<f32> {
         let color2 = color_palette(u32(floor(iterations)) + 1);
         
         let fract: f32 = iterations % 1;
-        color = color1 * (1.0 - fract) + color2 * fract;
+        color = mix_colors(color1, color2, fract);
 
         if (CUSTOM_ADDITIONAL_COLORS) {
             let relative_iterations = iterations / f32(MAX_ITERATIONS);
@@ -53,13 +124,122 @@ fn main(vertex: VertexInput) -> @location(0) vec4<f32> {
             color += vec3(0.0, 0.0, 0.6 - relative_iterations);
             color += vec3(0.3 * sin(position.y), 0.0, 0.3 * sin(position.y));
         }
+
+        if (COLORING != COLORING_ESCAPE_TIME) {
+            color = structure_color(color, escape);
+        }
     }
 
+    return output_color(color);
+}
+
+fn output_color(color: vec3<f32>) -> vec4<f32> {
+    if (ENCODE_SRGB) {
+        return vec4<f32>(linear_to_srgb(color), 1.0);
+    }
 
     return vec4<f32>(color, 1.0);
 }
 
-fn mandelbrot(position: vec2<f32>, max_iterations: u32) -> f32 {
+// Outputs the raw escape data instead of a color, for exporting it
+@fragment
+fn iteration_data(vertex: VertexInput) -> @location(0) vec4<f32> {
+    if (FRACTAL == FRACTAL_NEWTON) {
+        let convergence = newton(camera_position(vertex));
+        return vec4<f32>(convergence.iterations, 0.0, 0.0, 1.0);
+    }
+
+    let escape = escape_time(camera_position(vertex));
+
+    return vec4<f32>(escape.iterations, escape.distance, 0.0, 1.0);
+}
+
+// Darkens the escape time color (or white) where the binary decomposition is "negative" or on field lines
+fn structure_color(escape_time_color: vec3<f32>, escape: Escape) -> vec3<f32> {
+    var color = vec3(1.0);
+    var dark = vec3(0.0);
+    if (COMBINE_ESCAPE_TIME) {
+        color = escape_time_color;
+        dark = escape_time_color * 0.25;
+    }
+
+    if (COLORING == COLORING_BINARY_DECOMPOSITION) {
+        if (escape.z.y < 0.0) {
+            color = dark;
+        }
+    } else if (COLORING == COLORING_FIELD_LINES) {
+        // The argument of z, from 0 to 1. Each iteration band contains twice as many rays as the one outside of it.
+        let angle = atan2(escape.z.y, escape.z.x) / (2.0 * PI) + 0.5;
+        var line_distance = abs(fract(angle * 2.0 + 0.5) - 0.5);
+        if (SMOOTH_SHADING) {
+            line_distance = min(line_distance, abs(fract(escape.iterations + 0.5) - 0.5));
+        }
+        if (line_distance < FIELD_LINE_WIDTH) {
+            color = dark;
+        }
+    }
+
+    return color;
+}
+
+fn camera_position(vertex: VertexInput) -> vec2<f32> {
+    var position = vertex.coord;
+
+    // Camera scale
+    position *= camera.size;
+
+    // Camera offset
+    position += camera.position;
+
+    return position;
+}
+
+// Does the same conversion an sRGB render target would do when writing to it
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let clamped = clamp(color, vec3(0.0), vec3(1.0));
+    let lower = clamped * 12.92;
+    let higher = 1.055 * pow(clamped, vec3(1.0 / 2.4)) - 0.055;
+    return select(higher, lower, clamped <= vec3(0.0031308));
+}
+
+fn escape_time(position: vec2<f32>) -> Escape {
+    if (FRACTAL == FRACTAL_CUSTOM) {
+        return iterate_step(position, MAX_ITERATIONS);
+    }
+
+    return mandelbrot(position, MAX_ITERATIONS);
+}
+
+// Iterates the step function, without the optimizations and the distance estimate of `mandelbrot`
+fn iterate_step(c: vec2<f32>, max_iterations: u32) -> Escape {
+    var escape_radius_squared = 4.0;
+    if (SMOOTH_SHADING) {
+        escape_radius_squared = 1000.0;
+    }
+
+    var z = vec2(0.0);
+    var i: u32 = 0;
+    while (dot(z, z) <= escape_radius_squared && i < max_iterations) {
+        z = step(z, c);
+        i += u32(1);
+    }
+
+    if (i == max_iterations) {
+        return Escape(f32(max_iterations), 0.0, vec2(0.0));
+    }
+
+    if (SMOOTH_SHADING) {
+        // Assumes that the step grows like z^2, as the built-in one does
+        let log_zn = log(dot(z, z)) / 2.0;
+        let nu = log(log_zn / log(2.0)) / log(2.0);
+
+        return Escape(f32(i) + 1.0 - nu, 0.0, z);
+    }
+
+    return Escape(f32(i), 0.0, z);
+}
+
+fn mandelbrot(position: vec2<f32>, max_iterations: u32) -> Escape {
     var x0: f32 = position.x;
     var y0: f32 = position.y;
 
@@ -68,7 +248,7 @@ fn mandelbrot(position: vec2<f32>, max_iterations: u32) -> f32 {
     let left = q * (q + (x0 - 0.25));
     let right = 0.25 * y0 * y0;
     if (left <= right) {
-        return f32(max_iterations);
+        return Escape(f32(max_iterations), 0.0, vec2(0.0));
     }
 
     var x: f32 = 0.0;
@@ -77,6 +257,10 @@ fn mandelbrot(position: vec2<f32>, max_iterations: u32) -> f32 {
     var y2: f32 = 0.0;
     var i: u32 = 0;
 
+    // Derivative of z with respect to c, for the distance estimate
+    var dx: f32 = 0.0;
+    var dy: f32 = 0.0;
+
     var escape_time: f32;
     if (SMOOTH_SHADING) {
         escape_time = 1000.0;
@@ -85,6 +269,11 @@ fn mandelbrot(position: vec2<f32>, max_iterations: u32) -> f32 {
     }
 
     while (x2 + y2 <= escape_time && i < max_iterations) {
+        // dz = 2 * z * dz + 1
+        let new_dx = 2.0 * (x * dx - y * dy) + 1.0;
+        dy = 2.0 * (x * dy + y * dx);
+        dx = new_dx;
+
         y = (x + x) * y + y0;
         x = x2 - y2 + x0;
         x2 = x * x;
@@ -92,50 +281,244 @@ fn mandelbrot(position: vec2<f32>, max_iterations: u32) -> f32 {
         i += u32(1);
     }
 
+    if (i == max_iterations) {
+        return Escape(f32(max_iterations), 0.0, vec2(0.0));
+    }
+
+    // |z| * ln|z| / |dz|
+    let abs_z = sqrt(x2 + y2);
+    let distance = abs_z * log(abs_z) / length(vec2(dx, dy));
+
     if (SMOOTH_SHADING) {
         // Compute fractional iteration count for smooth shading
         let log_zn = log(x2 + y2) / 2.0;
         let nu = log(log_zn / log(2.0)) / log(2.0);
 
-        return f32(i) + 1.0 - nu;
+        return Escape(f32(i) + 1.0 - nu, distance, vec2(x, y));
     }
 
-    return f32(i);
+    return Escape(f32(i), distance, vec2(x, y));
+}
+
+// Colors a point by the root it converges to, darker the longer it takes
+fn newton_color(position: vec2<f32>) -> vec3<f32> {
+    let convergence = newton(position);
+    if (convergence.root == polynomial.degree) {
+        return vec3(0.0);
+    }
+
+    let offset = u32(NEWTON_PALETTE_OFFSET * f32(palette.length));
+    let root_color = color_palette(offset + convergence.root * palette.length / polynomial.degree);
+    return root_color * pow(NEWTON_SHADING, convergence.iterations);
+}
+
+// Newton's method for the polynomial. The step z - p(z) / p'(z) is z - 1 / sum(1 / (z - root)).
+fn newton(position: vec2<f32>) -> NewtonConvergence {
+    var z = position;
+
+    for (var i = 0u; i < MAX_ITERATIONS; i++) {
+        var reciprocal_sum = vec2(0.0);
+        for (var root = 0u; root < polynomial.degree; root++) {
+            let difference = z - polynomial.roots[root].xy;
+            let distance = length(difference);
+            if (distance < NEWTON_TOLERANCE) {
+                // Newton's method converges quadratically, so the logarithm of the distance doubles with every step
+                let fraction = clamp(log2(log(distance) / log(NEWTON_TOLERANCE)), 0.0, 1.0);
+                return NewtonConvergence(root, f32(i) + 1.0 - fraction);
+            }
+            reciprocal_sum += complex_reciprocal(difference);
+        }
+
+        z -= complex_reciprocal(reciprocal_sum);
+    }
+
+    return NewtonConvergence(polynomial.degree, f32(MAX_ITERATIONS));
+}
+
+fn complex_reciprocal(z: vec2<f32>) -> vec2<f32> {
+    return vec2(z.x, -z.y) / dot(z, z);
 }
 
 fn color_palette(i: u32) -> vec3<f32> {
-    let n = i % 16;
-    if (n == 0) {
-        return vec3(66.0 / 255.0, 30.0 / 255.0, 15.0 / 255.0);
-    } else if (n == 1) {
-        return vec3(25.0 / 255.0, 7.0 / 255.0, 26.0 / 255.0);
-    } else if (n == 2) {
-        return vec3(9.0 / 255.0, 1.0 / 255.0, 47.0 / 255.0);
-    } else if (n == 3) {
-        return vec3(4.0 / 255.0, 4.0 / 255.0, 73.0 / 255.0);
-    } else if (n == 4) {
-        return vec3(0.0 / 255.0, 7.0 / 255.0, 100.0 / 255.0);
-    } else if (n == 5) {
-        return vec3(12.0 / 255.0, 44.0 / 255.0, 138.0 / 255.0);
-    } else if (n == 6) {
-        return vec3(24.0 / 255.0, 82.0 / 255.0, 177.0 / 255.0);
-    } else if (n == 7) {
-        return vec3(57.0 / 255.0, 125.0 / 255.0, 209.0 / 255.0);
-    } else if (n == 8) {
-        return vec3(134.0 / 255.0, 181.0 / 255.0, 229.0 / 255.0);
-    } else if (n == 9) {
-        return vec3(211.0 / 255.0, 236.0 / 255.0, 248.0 / 255.0);
-    } else if (n == 10) {
-        return vec3(241.0 / 255.0, 233.0 / 255.0, 191.0 / 255.0);
-    } else if (n == 11) {
-        return vec3(248.0 / 255.0, 201.0 / 255.0, 95.0 / 255.0);
-    } else if (n == 12) {
-        return vec3(255.0 / 255.0, 170.0 / 255.0, 0.0 / 255.0);
-    } else if (n == 13) {
-        return vec3(204.0 / 255.0, 128.0 / 255.0, 0.0 / 255.0);
-    } else if (n == 14) {
-        return vec3(153.0 / 255.0, 87.0 / 255.0, 0.0 / 255.0);
-    } else {
-        return vec3(106.0 / 255.0, 52.0 / 255.0, 3.0 / 255.0);
+    return palette.colors[i % palette.length].rgb;
+}
+
+// Blends two linear colors in the color space of the palette, see `PaletteInterpolation::mix`
+fn mix_colors(color1: vec3<f32>, color2: vec3<f32>, t: f32) -> vec3<f32> {
+    var color: vec3<f32>;
+    switch (palette.interpolation) {
+        case INTERPOLATION_OKLAB: {
+            color = oklab_to_linear_srgb(mix(linear_srgb_to_oklab(color1), linear_srgb_to_oklab(color2), t));
+        }
+        case INTERPOLATION_OKLCH: {
+            let lch1 = lab_to_lch(linear_srgb_to_oklab(color1));
+            let lch2 = lab_to_lch(linear_srgb_to_oklab(color2));
+            color = oklab_to_linear_srgb(lch_to_lab(mix_polar(lch1, lch2, t, OKLCH_ACHROMATIC)));
+        }
+        case INTERPOLATION_HSLUV: {
+            let hsluv1 = linear_srgb_to_hsluv(color1);
+            let hsluv2 = linear_srgb_to_hsluv(color2);
+            color = hsluv_to_linear_srgb(mix_polar(hsluv1, hsluv2, t, HSLUV_ACHROMATIC));
+        }
+        default: {
+            color = mix(color1, color2, t);
+        }
     }
-}
\ No newline at end of file
+
+    return clamp(color, vec3(0.0), vec3(1.0));
+}
+
+// Below this OKLCh chroma or HSLuv saturation, the hue of a color is meaningless
+const OKLCH_ACHROMATIC: f32 = 1e-4;
+const HSLUV_ACHROMATIC: f32 = 1e-2;
+
+// Blends (lightness, chroma, hue) coordinates the shorter way around the hue circle.
+// Colors without chroma take the hue of the other color.
+fn mix_polar(color1: vec3<f32>, color2: vec3<f32>, t: f32, achromatic: f32) -> vec3<f32> {
+    var hue1 = color1.z;
+    var hue2 = color2.z;
+    if (color1.y < achromatic) {
+        hue1 = hue2;
+    } else if (color2.y < achromatic) {
+        hue2 = hue1;
+    }
+
+    let difference = hue2 - hue1 + PI;
+    let hue_distance = difference - 2.0 * PI * floor(difference / (2.0 * PI)) - PI;
+    return vec3(mix(color1.xy, color2.xy, t), hue1 + hue_distance * t);
+}
+
+// See `color.rs`
+fn linear_srgb_to_oklab(color: vec3<f32>) -> vec3<f32> {
+    let l = cbrt(dot(vec3(0.4122214708, 0.5363325363, 0.0514459929), color));
+    let m = cbrt(dot(vec3(0.2119034982, 0.6806995451, 0.1073969566), color));
+    let s = cbrt(dot(vec3(0.0883024619, 0.2817188376, 0.6299787005), color));
+    let lms = vec3(l, m, s);
+
+    return vec3(
+        dot(vec3(0.2104542553, 0.7936177850, -0.0040720468), lms),
+        dot(vec3(1.9779984951, -2.4285922050, 0.4505937099), lms),
+        dot(vec3(0.0259040371, 0.7827717662, -0.8086757660), lms),
+    );
+}
+
+fn oklab_to_linear_srgb(lab: vec3<f32>) -> vec3<f32> {
+    let l = dot(vec3(1.0, 0.3963377774, 0.2158037573), lab);
+    let m = dot(vec3(1.0, -0.1055613458, -0.0638541728), lab);
+    let s = dot(vec3(1.0, -0.0894841775, -1.2914855480), lab);
+    let lms = vec3(l * l * l, m * m * m, s * s * s);
+
+    return vec3(
+        dot(vec3(4.0767416621, -3.3077115913, 0.2309699292), lms),
+        dot(vec3(-1.2684380046, 2.6097574011, -0.3413193965), lms),
+        dot(vec3(-0.0041960863, -0.7034186147, 1.7076147010), lms),
+    );
+}
+
+fn lab_to_lch(lab: vec3<f32>) -> vec3<f32> {
+    return vec3(lab.x, length(lab.yz), atan2(lab.z, lab.y));
+}
+
+fn lch_to_lab(lch: vec3<f32>) -> vec3<f32> {
+    return vec3(lch.x, lch.y * cos(lch.z), lch.y * sin(lch.z));
+}
+
+fn cbrt(x: f32) -> f32 {
+    return sign(x) * pow(abs(x), 1.0 / 3.0);
+}
+
+// The constants of HSLuv, see `color.rs`
+const HSLUV_REFERENCE_U: f32 = 0.19783000664283;
+const HSLUV_REFERENCE_V: f32 = 0.46831999493879;
+const HSLUV_KAPPA: f32 = 903.2962962;
+const HSLUV_EPSILON: f32 = 0.0088564516;
+// Rows of the matrix from XYZ to linear sRGB
+const XYZ_TO_LINEAR_SRGB = array(
+    vec3(3.240969941904521, -1.537383177570093, -0.498610760293),
+    vec3(-0.96924363628087, 1.87596750150772, 0.041555057407175),
+    vec3(0.055630079696993, -0.20397695888897, 1.056971514242878),
+);
+
+// (lightness, saturation, hue), in the same order as `lab_to_lch`
+fn linear_srgb_to_hsluv(color: vec3<f32>) -> vec3<f32> {
+    let x = dot(vec3(0.41239079926595, 0.35758433938387, 0.18048078840183), color);
+    let y = dot(vec3(0.21263900587151, 0.71516867876775, 0.072192315360733), color);
+    let z = dot(vec3(0.019330818715591, 0.11919477979462, 0.95053215224966), color);
+
+    var lightness = 116.0 * pow(y, 1.0 / 3.0) - 16.0;
+    if (y <= HSLUV_EPSILON) {
+        lightness = y * HSLUV_KAPPA;
+    }
+    let denominator = x + 15.0 * y + 3.0 * z;
+    if (lightness < 1e-8 || denominator == 0.0) {
+        return vec3(0.0);
+    }
+    if (lightness > 100.0 - 1e-8) {
+        return vec3(100.0, 0.0, 0.0);
+    }
+    let u = 13.0 * lightness * (4.0 * x / denominator - HSLUV_REFERENCE_U);
+    let v = 13.0 * lightness * (9.0 * y / denominator - HSLUV_REFERENCE_V);
+
+    let lch = lab_to_lch(vec3(lightness, u, v));
+    return vec3(lightness, lch.y / hsluv_max_chroma(lightness, lch.z) * 100.0, lch.z);
+}
+
+fn hsluv_to_linear_srgb(hsluv: vec3<f32>) -> vec3<f32> {
+    let lightness = hsluv.x;
+    if (lightness < 1e-8) {
+        return vec3(0.0);
+    }
+    if (lightness > 100.0 - 1e-8) {
+        return vec3(1.0);
+    }
+
+    let chroma = hsluv_max_chroma(lightness, hsluv.z) * hsluv.y / 100.0;
+    let luv = lch_to_lab(vec3(lightness, chroma, hsluv.z));
+
+    let u = luv.y / (13.0 * lightness) + HSLUV_REFERENCE_U;
+    let v = luv.z / (13.0 * lightness) + HSLUV_REFERENCE_V;
+    var y = pow((lightness + 16.0) / 116.0, 3.0);
+    if (lightness <= 8.0) {
+        y = lightness / HSLUV_KAPPA;
+    }
+    let x = 9.0 * y * u / (4.0 * v);
+    let z = (9.0 * y - 15.0 * v * y - v * x) / (3.0 * v);
+
+    let xyz = vec3(x, y, z);
+    return vec3(dot(XYZ_TO_LINEAR_SRGB[0], xyz), dot(XYZ_TO_LINEAR_SRGB[1], xyz), dot(XYZ_TO_LINEAR_SRGB[2], xyz));
+}
+
+// The largest CIELUV chroma with the given lightness and hue that is still inside the sRGB gamut
+fn hsluv_max_chroma(lightness: f32, hue: f32) -> f32 {
+    let sub1 = pow(lightness + 16.0, 3.0) / 1560896.0;
+    var sub2 = lightness / HSLUV_KAPPA;
+    if (sub1 > HSLUV_EPSILON) {
+        sub2 = sub1;
+    }
+
+    // Constant arrays can only be indexed by constants
+    var rows = XYZ_TO_LINEAR_SRGB;
+    var max_chroma = 1e10;
+    for (var row = 0u; row < 3u; row++) {
+        let m = rows[row];
+        for (var t = 0.0; t <= 1.0; t += 1.0) {
+            // Where the channel is 0 or 1, as a line in the chroma plane
+            let top1 = (284517.0 * m.x - 94839.0 * m.z) * sub2;
+            let top2 = (838422.0 * m.z + 769860.0 * m.y + 731718.0 * m.x) * lightness * sub2 - 769860.0 * t * lightness;
+            let bottom = (632260.0 * m.z - 126452.0 * m.y) * sub2 + 126452.0 * t;
+
+            let length = (top2 / bottom) / (sin(hue) - (top1 / bottom) * cos(hue));
+            if (length >= 0.0) {
+                max_chroma = min(max_chroma, length);
+            }
+        }
+    }
+
+    return max_chroma;
+}
+
+// The iteration step of `Fractal::Custom`. `StepFunction` replaces the body of this function.
+fn step(z: vec2<f32>, c: vec2<f32>) -> vec2<f32> {
+    return vec2(z.x * z.x - z.y * z.y, 2.0 * z.x * z.y) + c;
+}
+// End of step function
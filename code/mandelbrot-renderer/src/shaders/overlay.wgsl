@@ -0,0 +1,33 @@
+// Draws an image like a heads-up display over a rendered frame, see `overlay.rs`
+
+@group(0)
+@binding(0)
+var image: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var image_sampler: sampler;
+
+// The left, top, right and bottom edge of the image in clip space
+@group(0)
+@binding(2)
+var<uniform> rect: vec4<f32>;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vertex_main(@location(0) position: vec2<f32>) -> VertexOutput {
+    var result: VertexOutput;
+    // Texture coordinates go from the top left to the bottom right
+    result.uv = vec2(position.x, -position.y) * 0.5 + 0.5;
+    result.position = vec4<f32>(mix(rect.xy, rect.zw, result.uv), 0.0, 1.0);
+    return result;
+}
+
+@fragment
+fn fragment_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(image, image_sampler, vertex.uv);
+}
@@ -0,0 +1,28 @@
+// Stretches a frame that was rendered at a lower resolution over the whole render target, see `quality.rs`
+
+@group(0)
+@binding(0)
+var frame: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var frame_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vertex_main(@location(0) position: vec2<f32>) -> VertexOutput {
+    var result: VertexOutput;
+    result.position = vec4<f32>(position, 0.0, 1.0);
+    // Texture coordinates go from the top left to the bottom right
+    result.uv = vec2(position.x, -position.y) * 0.5 + 0.5;
+    return result;
+}
+
+@fragment
+fn fragment_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(frame, frame_sampler, vertex.uv);
+}
@@ -0,0 +1,280 @@
+//! User-defined iteration steps for [`crate::Fractal::Custom`].
+//!
+//! The body of `fn step(z: vec2<f32>, c: vec2<f32>) -> vec2<f32>` is spliced into `frag.wgsl` in place of the
+//! built-in `z^2 + c`, so it can use the helper functions of the shader. Complex numbers are `vec2<f32>`s of the real
+//! and imaginary part. Orbits escape once `|z| > 2`, or `|z|^2 > 1000` with smooth shading.
+//!
+//! The whole shader is validated with naga before it is handed to wgpu, and errors are reported as [`ShaderDiagnostic`]s
+//! with locations inside the body, e.g. for showing them next to an editor.
+
+use std::{borrow::Cow, error::Error, fmt, sync::OnceLock};
+
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+
+/// The fragment shader with the built-in step function
+pub(crate) const FRAGMENT_SHADER: &str = include_str!("shaders/frag.wgsl");
+
+/// Follows the closing brace of the step function in `frag.wgsl`
+const STEP_FUNCTION_END: &str = "// End of step function";
+
+/// A validated body of the step function, see the [module documentation](self)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StepFunction {
+    body: String,
+}
+
+impl StepFunction {
+    pub const SIGNATURE: &'static str = "fn step(z: vec2<f32>, c: vec2<f32>) -> vec2<f32>";
+
+    /// Validates the body by compiling the whole fragment shader with it
+    pub fn new(body: impl Into<String>) -> Result<Self, ShaderDiagnostic> {
+        let step_function = Self { body: body.into() };
+        step_function.validate()?;
+
+        Ok(step_function)
+    }
+
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    fn validate(&self) -> Result<(), ShaderDiagnostic> {
+        self.check_braces()?;
+
+        let (source, body_offset) = self.splice();
+        let diagnostic = |message: String, notes: Vec<String>, spans: Vec<(naga::Span, String)>| ShaderDiagnostic {
+            message,
+            notes,
+            labels: spans
+                .into_iter()
+                .map(|(span, message)| DiagnosticLabel {
+                    location: self.location(&source, body_offset, span),
+                    message,
+                })
+                .collect(),
+        };
+
+        let module = naga::front::wgsl::parse_str(&source).map_err(|error| {
+            let spans = error.labels().map(|(span, message)| (span, message.to_owned())).collect();
+            diagnostic(error.message().to_owned(), Vec::new(), spans)
+        })?;
+
+        // Anything declared next to `step` would not match the pipeline layout, or shadow the helpers of the shader
+        if declarations(&module) != declarations(builtin_module()) {
+            return Err(diagnostic(
+                "The step function body must not declare bindings, overrides, functions or entry points".to_owned(),
+                Vec::new(),
+                Vec::new(),
+            ));
+        }
+
+        let step = module
+            .functions
+            .iter()
+            .find(|(_, function)| function.name.as_deref() == Some("step"))
+            .map(|(_, function)| function);
+        if !step.is_some_and(|step| returns_on_every_path(&step.body)) {
+            return Err(diagnostic("The step function must return a value on every path".to_owned(), Vec::new(), Vec::new()));
+        }
+
+        Validator::new(ValidationFlags::all(), Capabilities::default())
+            .validate(&module)
+            .map_err(|error| {
+                // The causes of validation errors are the most specific, e.g. which types did not match
+                let mut notes = Vec::new();
+                let mut cause = error.as_inner().source();
+                while let Some(next) = cause {
+                    notes.push(next.to_string());
+                    cause = next.source();
+                }
+                diagnostic(error.as_inner().to_string(), notes, error.spans().cloned().collect())
+            })?;
+
+        Ok(())
+    }
+
+    /// Rejects bodies that close `step` and continue at the top level of the shader
+    fn check_braces(&self) -> Result<(), ShaderDiagnostic> {
+        let mut depth = 0_i32;
+        let mut chars = self.body.char_indices().peekable();
+        while let Some((index, character)) = chars.next() {
+            match character {
+                '/' if chars.next_if(|(_, next)| *next == '/').is_some() => {
+                    while chars.next_if(|(_, next)| *next != '\n').is_some() {}
+                }
+                '/' if chars.next_if(|(_, next)| *next == '*').is_some() => {
+                    // Block comments nest in WGSL
+                    let mut comment_depth = 1;
+                    while comment_depth > 0 {
+                        match chars.next() {
+                            Some((_, '*')) if chars.next_if(|(_, next)| *next == '/').is_some() => comment_depth -= 1,
+                            Some((_, '/')) if chars.next_if(|(_, next)| *next == '*').is_some() => comment_depth += 1,
+                            Some(_) => {}
+                            None => break,
+                        }
+                    }
+                }
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => {}
+            }
+
+            if depth < 0 {
+                let before = &self.body[..index];
+                let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+                return Err(ShaderDiagnostic {
+                    message: "The step function body must not close the step function".to_owned(),
+                    notes: Vec::new(),
+                    labels: vec![DiagnosticLabel {
+                        location: Some(SourceLocation {
+                            line: before.matches('\n').count() as u32 + 1,
+                            column: before[line_start..].chars().count() as u32 + 1,
+                            length: 1,
+                        }),
+                        message: "unmatched closing brace".to_owned(),
+                    }],
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The fragment shader with this step function, and the byte offset of the body in it
+    fn splice(&self) -> (String, usize) {
+        // The body starts on the line after the signature, whatever the line endings of the checkout are
+        let signature = FRAGMENT_SHADER
+            .find(Self::SIGNATURE)
+            .expect("frag.wgsl is missing the step function");
+        let start = FRAGMENT_SHADER[signature..]
+            .find('\n')
+            .expect("frag.wgsl is missing the body of the step function")
+            + signature
+            + 1;
+        let marker = FRAGMENT_SHADER[start..]
+            .find(STEP_FUNCTION_END)
+            .expect("frag.wgsl is missing the end of the step function")
+            + start;
+        let end = FRAGMENT_SHADER[start..marker]
+            .rfind('}')
+            .expect("frag.wgsl is missing the closing brace of the step function")
+            + start;
+
+        let source = format!("{}{}\n{}", &FRAGMENT_SHADER[..start], self.body, &FRAGMENT_SHADER[end..]);
+        (source, start)
+    }
+
+    /// Converts a span in the spliced shader into a location in the body
+    fn location(&self, source: &str, body_offset: usize, span: naga::Span) -> Option<SourceLocation> {
+        let range = span.to_range()?;
+        if range.start < body_offset || range.end > body_offset + self.body.len() {
+            return None;
+        }
+
+        let before = &source[body_offset..range.start];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        Some(SourceLocation {
+            line: before.matches('\n').count() as u32 + 1,
+            column: before[line_start..].chars().count() as u32 + 1,
+            length: range.len() as u32,
+        })
+    }
+}
+
+/// The built-in fragment shader, parsed once
+fn builtin_module() -> &'static naga::Module {
+    static MODULE: OnceLock<naga::Module> = OnceLock::new();
+    MODULE.get_or_init(|| naga::front::wgsl::parse_str(FRAGMENT_SHADER).expect("frag.wgsl is invalid"))
+}
+
+/// Everything at the top level of a shader that ends up in the pipeline or could replace a helper of the shader
+fn declarations(module: &naga::Module) -> Vec<String> {
+    let global_variables = module.global_variables.iter().map(|(_, variable)| {
+        format!("var {:?} {:?} {:?}", variable.name, variable.space, variable.binding)
+    });
+    let overrides = module
+        .overrides
+        .iter()
+        .map(|(_, override_)| format!("override {:?} {:?}", override_.name, override_.id));
+    let functions = module
+        .functions
+        .iter()
+        .map(|(_, function)| format!("fn {:?}", function.name));
+    let entry_points = module
+        .entry_points
+        .iter()
+        .map(|entry_point| format!("entry point {} {:?}", entry_point.name, entry_point.stage));
+
+    global_variables.chain(overrides).chain(functions).chain(entry_points).collect()
+}
+
+/// naga does not check this for functions with a return type, and backends would fail to compile them
+fn returns_on_every_path(block: &naga::Block) -> bool {
+    use naga::Statement;
+
+    match block.last() {
+        Some(Statement::Return { value }) => value.is_some(),
+        // Loops can only be left by returning or breaking, which is not worth distinguishing here
+        Some(Statement::Kill | Statement::Loop { .. }) => true,
+        Some(Statement::Block(block)) => returns_on_every_path(block),
+        Some(Statement::If { accept, reject, .. }) => returns_on_every_path(accept) && returns_on_every_path(reject),
+        Some(Statement::Switch { cases, .. }) => cases
+            .iter()
+            .all(|case| case.fall_through || returns_on_every_path(&case.body)),
+        _ => false,
+    }
+}
+
+/// The fragment shader with the given step function, or the built-in one
+pub(crate) fn fragment_shader_source(step_function: Option<&StepFunction>) -> Cow<'static, str> {
+    match step_function {
+        Some(step_function) => Cow::Owned(step_function.splice().0),
+        None => Cow::Borrowed(FRAGMENT_SHADER),
+    }
+}
+
+/// A compile error of a [`StepFunction`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShaderDiagnostic {
+    pub message: String,
+    /// The causes of the error, from the most general to the most specific one
+    pub notes: Vec<String>,
+    /// The parts of the source the error refers to
+    pub labels: Vec<DiagnosticLabel>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiagnosticLabel {
+    /// `None` if the label refers to a part of the shader outside of the step function body
+    pub location: Option<SourceLocation>,
+    pub message: String,
+}
+
+/// A location in the body of a step function
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// 1-based
+    pub line: u32,
+    /// 1-based, counted in characters
+    pub column: u32,
+    /// In bytes
+    pub length: u32,
+}
+
+impl fmt::Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)?;
+        for label in &self.labels {
+            match label.location {
+                Some(location) => write!(f, "\n  {}:{}: {}", location.line, location.column, label.message)?,
+                None => write!(f, "\n  (shader template): {}", label.message)?,
+            }
+        }
+        for note in &self.notes {
+            write!(f, "\n  note: {note}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ShaderDiagnostic {}
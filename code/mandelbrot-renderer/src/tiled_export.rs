@@ -0,0 +1,403 @@
+//! Export of images that are larger than a single GPU texture.
+//!
+//! The image is split into square tiles, which are rendered one after another and read back.
+//! Each tile is rendered with `overlap` additional pixels on every side. With supersampling, the anti-aliasing filter
+//! reaches into the neighbouring pixels, so an overlap of at least one pixel is needed for tile borders to be seamless.
+//!
+//! Tiles are either stitched into one PNG image, which is streamed row by row so that the whole image never has to
+//! fit into memory, or written as a [Deep Zoom Image](https://learn.microsoft.com/en-us/previous-versions/windows/silverlight/dotnet-windows-silverlight/cc645077(v=vs.95))
+//! pyramid for zoomable viewers like OpenSeadragon. Every level of the pyramid is rendered at its own resolution.
+
+use std::io::Write;
+
+use anyhow::{bail, Result};
+use cgmath::Vector2;
+
+use crate::{
+    capture::png_encoder,
+    color::{linear_to_srgb, srgb_to_linear},
+    write_png, Camera, MandelbrotRenderer, PipelineOutput, Viewport,
+};
+
+/// Renders an image of arbitrary size tile by tile, see the [module documentation](self)
+#[derive(Copy, Clone, Debug)]
+pub struct TiledExport {
+    viewport: Viewport,
+    size: (u32, u32),
+    tile_size: u32,
+    overlap: u32,
+    supersampling: u32,
+}
+
+/// A rectangle in pixels of the exported image. It may extend beyond the image.
+#[derive(Copy, Clone, Debug)]
+struct Rect {
+    x: i64,
+    y: i64,
+    width: u32,
+    height: u32,
+}
+
+impl TiledExport {
+    /// The default tile size. Together with the default overlap, tiles are 512 pixels wide.
+    pub const DEFAULT_TILE_SIZE: u32 = 510;
+
+    /// Creates an export of the given view. As for the window, the zoom determines the horizontal extent of the image.
+    pub fn new(viewport: Viewport, size: (u32, u32)) -> Self {
+        Self {
+            viewport,
+            size,
+            tile_size: Self::DEFAULT_TILE_SIZE,
+            overlap: 1,
+            supersampling: 1,
+        }
+    }
+
+    /// The width and height of a tile in pixels, without the overlap
+    pub fn with_tile_size(mut self, tile_size: u32) -> Self {
+        self.tile_size = tile_size;
+        self
+    }
+
+    /// The number of pixels every tile shares with each of its neighbours
+    pub fn with_overlap(mut self, overlap: u32) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    /// Renders `supersampling * supersampling` samples per pixel, which are combined with a tent filter. `1` disables anti-aliasing.
+    pub fn with_supersampling(mut self, supersampling: u32) -> Self {
+        self.supersampling = supersampling;
+        self
+    }
+
+    pub fn viewport(&self) -> Viewport {
+        self.viewport
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn tile_size(&self) -> u32 {
+        self.tile_size
+    }
+
+    pub fn overlap(&self) -> u32 {
+        self.overlap
+    }
+
+    pub fn supersampling(&self) -> u32 {
+        self.supersampling
+    }
+
+    /// Renders the whole image and encodes it as a single PNG image with the view descriptor embedded
+    pub async fn write_png(&self, renderer: &mut MandelbrotRenderer, writer: impl Write) -> Result<()> {
+        self.validate(renderer)?;
+
+        let (width, height) = self.size;
+        let view = renderer.view_descriptor_for(self.viewport);
+        let mut encoder = png_encoder(writer, self.size, &view)?;
+        encoder.set_color(png::ColorType::Rgba);
+        let mut png_writer = encoder.write_header()?;
+        let mut stream = png_writer.stream_writer()?;
+
+        let (columns, rows) = tile_count(self.size, self.tile_size);
+        for row in 0..rows {
+            let band_height = self.tile_size.min(height - row * self.tile_size);
+            let mut band = vec![0; width as usize * band_height as usize * 4];
+
+            for column in 0..columns {
+                let tile = self.tile(self.size, (column, row));
+                let pixels = self.render_tile(renderer, self.size, tile, tile).await?;
+
+                // Copy the tile into its place within the band
+                let offset = tile.x as usize * 4;
+                let tile_row_bytes = tile.width as usize * 4;
+                for (y, tile_row) in pixels.chunks_exact(tile_row_bytes).enumerate() {
+                    let start = y * width as usize * 4 + offset;
+                    band[start..start + tile_row_bytes].copy_from_slice(tile_row);
+                }
+            }
+
+            stream.write_all(&band)?;
+            log::info!("Rendered tile row {}/{rows}", row + 1);
+        }
+        stream.finish()?;
+        png_writer.finish()?;
+
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn save_png(&self, renderer: &mut MandelbrotRenderer, path: impl AsRef<std::path::Path>) -> Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_png(renderer, file).await
+    }
+
+    /// The number of levels of the Deep Zoom pyramid.
+    /// The highest level has the full size, every level below has half the size of the one above, down to a single pixel.
+    pub fn dzi_levels(&self) -> u32 {
+        let max_dimension = self.size.0.max(self.size.1);
+        max_dimension.next_power_of_two().trailing_zeros() + 1
+    }
+
+    /// The size of the image at the given level of the Deep Zoom pyramid
+    pub fn dzi_level_size(&self, level: u32) -> (u32, u32) {
+        let scale = 1_u64 << (self.dzi_levels() - 1 - level);
+        (
+            (self.size.0 as u64).div_ceil(scale) as u32,
+            (self.size.1 as u64).div_ceil(scale) as u32,
+        )
+    }
+
+    /// The XML descriptor of the Deep Zoom pyramid
+    pub fn dzi_descriptor(&self) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<Image xmlns="http://schemas.microsoft.com/deepzoom/2008" Format="png" Overlap="{}" TileSize="{}">
+    <Size Width="{}" Height="{}"/>
+</Image>
+"#,
+            self.overlap, self.tile_size, self.size.0, self.size.1
+        )
+    }
+
+    /// Renders a Deep Zoom pyramid. The descriptor is written to `path` (usually ending in `.dzi`), and the tiles
+    /// into the directory `<name>_files` next to it, as `<level>/<column>_<row>.png`.
+    /// Every tile contains the view descriptor of the area it shows.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn save_dzi(&self, renderer: &mut MandelbrotRenderer, path: impl AsRef<std::path::Path>) -> Result<()> {
+        use anyhow::Context;
+
+        self.validate(renderer)?;
+
+        let path = path.as_ref();
+        let name = path
+            .file_stem()
+            .context("The path of the Deep Zoom descriptor has no file name")?
+            .to_string_lossy();
+        let tiles_directory = path.with_file_name(format!("{name}_files"));
+
+        std::fs::write(path, self.dzi_descriptor())?;
+
+        let levels = self.dzi_levels();
+        for level in (0..levels).rev() {
+            let level_size = self.dzi_level_size(level);
+            let level_directory = tiles_directory.join(level.to_string());
+            std::fs::create_dir_all(&level_directory)?;
+
+            let (columns, rows) = tile_count(level_size, self.tile_size);
+            for row in 0..rows {
+                for column in 0..columns {
+                    let tile = self.tile(level_size, (column, row));
+
+                    // Deep Zoom tiles contain the overlap, except at the borders of the image
+                    let x = (tile.x - self.overlap as i64).max(0);
+                    let y = (tile.y - self.overlap as i64).max(0);
+                    let crop = Rect {
+                        x,
+                        y,
+                        width: ((tile.x + tile.width as i64 + self.overlap as i64).min(level_size.0 as i64) - x) as u32,
+                        height: ((tile.y + tile.height as i64 + self.overlap as i64).min(level_size.1 as i64) - y) as u32,
+                    };
+
+                    let pixels = self.render_tile(renderer, level_size, tile, crop).await?;
+                    let view = renderer.view_descriptor_for(self.rect_viewport(level_size, crop));
+                    let file = std::io::BufWriter::new(std::fs::File::create(
+                        level_directory.join(format!("{column}_{row}.png")),
+                    )?);
+                    write_png(file, (crop.width, crop.height), &pixels, &view)?;
+                }
+            }
+
+            log::info!("Rendered level {level} ({}x{})", level_size.0, level_size.1);
+        }
+
+        Ok(())
+    }
+
+    fn validate(&self, renderer: &MandelbrotRenderer) -> Result<()> {
+        if self.size.0 == 0 || self.size.1 == 0 {
+            bail!("The exported image must not be empty");
+        }
+        if self.tile_size == 0 || self.supersampling == 0 {
+            bail!("The tile size and supersampling factor must be at least 1");
+        }
+
+        let max_texture_size = renderer.device.limits().max_texture_dimension_2d;
+        let texture_size = (self.tile_size as u64 + 2 * self.overlap as u64) * self.supersampling as u64;
+        if texture_size > max_texture_size as u64 {
+            bail!("Tiles of {texture_size} pixels including overlap and supersampling exceed the maximum texture size of {max_texture_size}");
+        }
+
+        Ok(())
+    }
+
+    /// The area of the tile at the given column and row, without overlap
+    fn tile(&self, image_size: (u32, u32), (column, row): (u32, u32)) -> Rect {
+        let x = column * self.tile_size;
+        let y = row * self.tile_size;
+
+        Rect {
+            x: x as i64,
+            y: y as i64,
+            width: self.tile_size.min(image_size.0 - x),
+            height: self.tile_size.min(image_size.1 - y),
+        }
+    }
+
+    /// The view showing the given area of an image with the given size
+    fn rect_viewport(&self, image_size: (u32, u32), rect: Rect) -> Viewport {
+        let (center, half_size) = self.rect_camera(image_size, rect);
+        Viewport::new(center, -half_size.0.log10())
+    }
+
+    /// The camera center and size showing the given area of an image with the given size
+    fn rect_camera(&self, (width, height): (u32, u32), rect: Rect) -> ((f32, f32), (f32, f32)) {
+        // The size of a pixel on the complex plane
+        let pixel_size = 2.0 * 10.0_f64.powf(-self.viewport.zoom_exponent as f64) / width as f64;
+
+        let offset = (
+            rect.x as f64 + rect.width as f64 / 2.0 - width as f64 / 2.0,
+            rect.y as f64 + rect.height as f64 / 2.0 - height as f64 / 2.0,
+        );
+        let center = (
+            self.viewport.center.0 as f64 + offset.0 * pixel_size,
+            // Pixel rows go down, the imaginary axis goes up
+            self.viewport.center.1 as f64 - offset.1 * pixel_size,
+        );
+        let half_size = (
+            rect.width as f64 * pixel_size / 2.0,
+            rect.height as f64 * pixel_size / 2.0,
+        );
+
+        (
+            (center.0 as f32, center.1 as f32),
+            (half_size.0 as f32, half_size.1 as f32),
+        )
+    }
+
+    /// Renders a tile including its overlap, and returns the 8-bit sRGB RGBA pixels of the `crop` area
+    async fn render_tile(
+        &self,
+        renderer: &mut MandelbrotRenderer,
+        image_size: (u32, u32),
+        tile: Rect,
+        crop: Rect,
+    ) -> Result<Vec<u8>> {
+        let overlap = self.overlap as i64;
+        let rect = Rect {
+            x: tile.x - overlap,
+            y: tile.y - overlap,
+            width: tile.width + 2 * self.overlap,
+            height: tile.height + 2 * self.overlap,
+        };
+
+        let (center, size) = self.rect_camera(image_size, rect);
+        let camera = Camera {
+            center: Vector2::from(center),
+            size: Vector2::from(size),
+        };
+        let samples_size = (rect.width * self.supersampling, rect.height * self.supersampling);
+        let samples = renderer
+            .render_offscreen(camera, samples_size, PipelineOutput::Color)
+            .await?;
+
+        let pixels = if self.supersampling > 1 {
+            downsample(&samples, samples_size, self.supersampling)
+        } else {
+            samples
+        };
+
+        // Cut the requested area out of the rendered one
+        let row_bytes = rect.width as usize * 4;
+        let crop_row_bytes = crop.width as usize * 4;
+        let offset = (crop.x - rect.x) as usize * 4;
+        Ok(pixels
+            .chunks_exact(row_bytes)
+            .skip((crop.y - rect.y) as usize)
+            .take(crop.height as usize)
+            .flat_map(|row| &row[offset..offset + crop_row_bytes])
+            .copied()
+            .collect())
+    }
+}
+
+/// The number of columns and rows of tiles needed to cover an image
+fn tile_count((width, height): (u32, u32), tile_size: u32) -> (u32, u32) {
+    (width.div_ceil(tile_size), height.div_ceil(tile_size))
+}
+
+/// Combines `factor * factor` samples into one pixel, using a tent filter that is two pixels wide.
+/// Filtering happens in linear light, and samples outside of the image are ignored.
+fn downsample(samples: &[u8], (width, height): (u32, u32), factor: u32) -> Vec<u8> {
+    let linear: Vec<f32> = samples
+        .chunks_exact(4)
+        .flat_map(|sample| {
+            let [r, g, b, a] = [sample[0], sample[1], sample[2], sample[3]];
+            [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a as f32 / 255.0]
+        })
+        .collect();
+
+    let (width, height) = (width as usize, height as usize);
+    let factor = factor as usize;
+    let (output_width, output_height) = (width / factor, height / factor);
+
+    // The filter is separable, so filter rows first and columns afterwards
+    let horizontal_weights = tent_weights(output_width, width, factor);
+    let mut horizontal = vec![0.0; output_width * height * 4];
+    for y in 0..height {
+        for (x, (start, weights)) in horizontal_weights.iter().enumerate() {
+            for (i, weight) in weights.iter().enumerate() {
+                let sample = ((y * width) + start + i) * 4;
+                let pixel = (y * output_width + x) * 4;
+                for channel in 0..4 {
+                    horizontal[pixel + channel] += weight * linear[sample + channel];
+                }
+            }
+        }
+    }
+
+    let vertical_weights = tent_weights(output_height, height, factor);
+    let mut pixels = vec![0.0; output_width * output_height * 4];
+    for (y, (start, weights)) in vertical_weights.iter().enumerate() {
+        for (i, weight) in weights.iter().enumerate() {
+            let row = &horizontal[(start + i) * output_width * 4..(start + i + 1) * output_width * 4];
+            let pixel_row = &mut pixels[y * output_width * 4..(y + 1) * output_width * 4];
+            for (pixel, sample) in pixel_row.iter_mut().zip(row) {
+                *pixel += weight * sample;
+            }
+        }
+    }
+
+    pixels
+        .chunks_exact(4)
+        .flat_map(|pixel| {
+            [
+                linear_to_srgb(pixel[0]),
+                linear_to_srgb(pixel[1]),
+                linear_to_srgb(pixel[2]),
+                (pixel[3] * 255.0).round() as u8,
+            ]
+        })
+        .collect()
+}
+
+/// For every pixel, returns the index of the first sample that contributes to it and the normalized weights of the contributing samples
+fn tent_weights(pixels: usize, samples: usize, factor: usize) -> Vec<(usize, Vec<f32>)> {
+    (0..pixels)
+        .map(|pixel| {
+            let center = (pixel as f32 + 0.5) * factor as f32;
+            let start = (pixel * factor).saturating_sub(factor);
+            let end = ((pixel + 2) * factor).min(samples);
+
+            let weights: Vec<f32> = (start..end)
+                .map(|sample| (1.0 - (sample as f32 + 0.5 - center).abs() / factor as f32).max(0.0))
+                .collect();
+            let total: f32 = weights.iter().sum();
+
+            (start, weights.into_iter().map(|weight| weight / total).collect())
+        })
+        .collect()
+}
@@ -0,0 +1,398 @@
+use anyhow::{anyhow, Context, Result};
+use web_time::Duration;
+
+use crate::{
+    Coloring, Fractal, PaletteInterpolation, Polynomial, ShaderConstants, StepFunction, DEFAULT_PALETTE_NAME,
+};
+
+/// A view onto the complex plane.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Viewport {
+    /// The point at the center of the view
+    pub center: (f32, f32),
+    /// The horizontal camera size is `10^-zoom_exponent`
+    pub zoom_exponent: f32,
+}
+
+impl Viewport {
+    pub fn new(center: (f32, f32), zoom_exponent: f32) -> Self {
+        Self {
+            center,
+            zoom_exponent,
+        }
+    }
+
+    /// Calculates the camera size as expected by [`crate::MandelbrotRenderer::render`] for a view of the given size in pixels.
+    pub fn camera_size(&self, (width, height): (u32, u32)) -> (f32, f32) {
+        let camera_size = 10.0_f32.powf(-self.zoom_exponent);
+
+        (camera_size, camera_size * height as f32 / width as f32)
+    }
+
+    /// Creates an animation that smoothly moves the camera from this view to the target view.
+    pub fn fly_to(&self, target: Viewport) -> FlyTo {
+        FlyTo::new(*self, target)
+    }
+
+    /// Applies a keyboard command to this view. `home` is the view [`Navigation::Reset`] returns to.
+    pub fn navigate(&self, navigation: Navigation, steps: NavigationSteps, home: Viewport) -> Viewport {
+        // The horizontal camera size is half the width of the view
+        let pan_distance = 2.0 * 10.0_f32.powf(-self.zoom_exponent) * steps.pan;
+        let pan = |(x, y): (f32, f32)| Viewport {
+            center: (self.center.0 + x * pan_distance, self.center.1 + y * pan_distance),
+            ..*self
+        };
+        let zoom = |zoom_exponent_delta: f32| Viewport {
+            zoom_exponent: self.zoom_exponent + zoom_exponent_delta,
+            ..*self
+        };
+
+        match navigation {
+            Navigation::PanLeft => pan((-1.0, 0.0)),
+            Navigation::PanRight => pan((1.0, 0.0)),
+            Navigation::PanUp => pan((0.0, 1.0)),
+            Navigation::PanDown => pan((0.0, -1.0)),
+            Navigation::ZoomIn => zoom(steps.zoom),
+            Navigation::ZoomOut => zoom(-steps.zoom),
+            Navigation::Reset => home,
+        }
+    }
+}
+
+/// A keyboard command that moves the camera, see [`Viewport::navigate`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Navigation {
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+    ZoomIn,
+    ZoomOut,
+    /// Returns to the initial view
+    Reset,
+}
+
+impl Navigation {
+    /// The command for a key, named like `KeyboardEvent.key` in browsers.
+    /// The arrow keys and WASD pan, `+` and `-` zoom (also without shift, as `=` and `_`), and `0` or Home resets.
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "ArrowLeft" | "a" | "A" => Some(Navigation::PanLeft),
+            "ArrowRight" | "d" | "D" => Some(Navigation::PanRight),
+            "ArrowUp" | "w" | "W" => Some(Navigation::PanUp),
+            "ArrowDown" | "s" | "S" => Some(Navigation::PanDown),
+            "+" | "=" => Some(Navigation::ZoomIn),
+            "-" | "_" => Some(Navigation::ZoomOut),
+            "0" | "Home" => Some(Navigation::Reset),
+            _ => None,
+        }
+    }
+}
+
+/// How far a single key press moves the camera
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NavigationSteps {
+    /// The distance panned, as a fraction of the width of the view
+    pub pan: f32,
+    /// The change of the zoom exponent
+    pub zoom: f32,
+}
+
+impl Default for NavigationSteps {
+    fn default() -> Self {
+        Self { pan: 0.1, zoom: 0.1 }
+    }
+}
+
+/// Everything needed to render a view again exactly as it was
+#[derive(Clone, Debug, PartialEq)]
+pub struct ViewDescriptor {
+    pub viewport: Viewport,
+    pub fractal: Fractal,
+    /// Only used by [`Fractal::Newton`]
+    pub polynomial: Polynomial,
+    /// Only used by [`Fractal::Custom`], `None` for the built-in step
+    pub step_function: Option<StepFunction>,
+    pub palette: String,
+    pub palette_interpolation: PaletteInterpolation,
+    pub shader_constants: ShaderConstants,
+}
+
+impl ViewDescriptor {
+    /// Converts this descriptor into key-value pairs, e.g. for storing it in PNG text chunks
+    pub fn to_key_values(&self) -> Vec<(&'static str, String)> {
+        let mut key_values = vec![
+            ("Mandelbrot.CenterRe", self.viewport.center.0.to_string()),
+            ("Mandelbrot.CenterIm", self.viewport.center.1.to_string()),
+            ("Mandelbrot.ZoomExponent", self.viewport.zoom_exponent.to_string()),
+            ("Mandelbrot.Fractal", self.fractal.name().to_owned()),
+            ("Mandelbrot.Palette", self.palette.clone()),
+            (
+                "Mandelbrot.PaletteInterpolation",
+                self.palette_interpolation.name().to_owned(),
+            ),
+            ("Mandelbrot.MaxIterations", self.shader_constants.max_iterations.to_string()),
+            ("Mandelbrot.SmoothShading", self.shader_constants.smooth_shading.to_string()),
+            (
+                "Mandelbrot.CustomAdditionalColors",
+                self.shader_constants.custom_additional_colors.to_string(),
+            ),
+            ("Mandelbrot.Coloring", self.shader_constants.coloring.name().to_owned()),
+            (
+                "Mandelbrot.CombineEscapeTime",
+                self.shader_constants.combine_escape_time.to_string(),
+            ),
+        ];
+        if self.fractal == Fractal::Newton {
+            key_values.push(("Mandelbrot.NewtonRoots", self.polynomial.to_string()));
+        }
+        if let (Fractal::Custom, Some(step_function)) = (self.fractal, &self.step_function) {
+            key_values.push(("Mandelbrot.StepFunction", step_function.body().to_owned()));
+        }
+
+        key_values
+    }
+
+    /// Parses a descriptor from key-value pairs created by [`ViewDescriptor::to_key_values`].
+    /// Unknown keys are ignored, everything except the viewport falls back to its default value.
+    pub fn from_key_values<'a>(key_values: impl IntoIterator<Item = (&'a str, &'a str)>) -> Result<Self> {
+        let mut center = (None, None);
+        let mut zoom_exponent = None;
+        let mut descriptor = ViewDescriptor {
+            viewport: Viewport::new((0.0, 0.0), 0.0),
+            fractal: Fractal::default(),
+            polynomial: Polynomial::default(),
+            step_function: None,
+            palette: DEFAULT_PALETTE_NAME.to_owned(),
+            palette_interpolation: PaletteInterpolation::default(),
+            shader_constants: ShaderConstants::default(),
+        };
+
+        for (key, value) in key_values {
+            let invalid = || format!("Invalid value `{value}` for `{key}`");
+            match key {
+                "Mandelbrot.CenterRe" => center.0 = Some(value.parse().with_context(invalid)?),
+                "Mandelbrot.CenterIm" => center.1 = Some(value.parse().with_context(invalid)?),
+                "Mandelbrot.ZoomExponent" => zoom_exponent = Some(value.parse().with_context(invalid)?),
+                "Mandelbrot.Fractal" => {
+                    descriptor.fractal = Fractal::from_name(value).ok_or_else(|| anyhow!(invalid()))?
+                }
+                "Mandelbrot.NewtonRoots" => descriptor.polynomial = value.parse().with_context(invalid)?,
+                "Mandelbrot.StepFunction" => {
+                    descriptor.step_function = Some(StepFunction::new(value).with_context(invalid)?)
+                }
+                "Mandelbrot.Palette" => descriptor.palette = value.to_owned(),
+                "Mandelbrot.PaletteInterpolation" => {
+                    descriptor.palette_interpolation =
+                        PaletteInterpolation::from_name(value).ok_or_else(|| anyhow!(invalid()))?
+                }
+                "Mandelbrot.MaxIterations" => {
+                    descriptor.shader_constants.max_iterations = value.parse().with_context(invalid)?
+                }
+                "Mandelbrot.SmoothShading" => {
+                    descriptor.shader_constants.smooth_shading = value.parse().with_context(invalid)?
+                }
+                "Mandelbrot.CustomAdditionalColors" => {
+                    descriptor.shader_constants.custom_additional_colors = value.parse().with_context(invalid)?
+                }
+                "Mandelbrot.Coloring" => {
+                    descriptor.shader_constants.coloring = Coloring::from_name(value).ok_or_else(|| anyhow!(invalid()))?
+                }
+                "Mandelbrot.CombineEscapeTime" => {
+                    descriptor.shader_constants.combine_escape_time = value.parse().with_context(invalid)?
+                }
+                _ => {}
+            }
+        }
+
+        let (Some(re), Some(im), Some(zoom_exponent)) = (center.0, center.1, zoom_exponent) else {
+            return Err(anyhow!("The view descriptor is missing its center or zoom"));
+        };
+        descriptor.viewport = Viewport::new((re, im), zoom_exponent);
+
+        Ok(descriptor)
+    }
+}
+
+/// Easing functions that map the linear progress of an animation to the progress along its path
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    #[default]
+    EaseInOut,
+}
+
+impl Easing {
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+        }
+    }
+}
+
+/// An animated camera transition between two views.
+///
+/// The camera follows the optimal path described by van Wijk and Nuij in
+/// "Smooth and efficient zooming and panning": It zooms out while moving towards the target, then zooms back in.
+/// Zoom is interpolated logarithmically, so every zoom level takes the same amount of time.
+#[derive(Copy, Clone, Debug)]
+pub struct FlyTo {
+    from: Viewport,
+    to: Viewport,
+    duration: Duration,
+    easing: Easing,
+    path: FlyToPath,
+}
+
+impl FlyTo {
+    /// The default trade-off between zooming and panning, as suggested by van Wijk and Nuij
+    pub const DEFAULT_CURVATURE: f64 = std::f64::consts::SQRT_2;
+
+    pub fn new(from: Viewport, to: Viewport) -> Self {
+        Self {
+            from,
+            to,
+            duration: Duration::from_secs(2),
+            easing: Easing::default(),
+            path: FlyToPath::new(from, to, Self::DEFAULT_CURVATURE),
+        }
+    }
+
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Sets how strongly the camera zooms out during the transition. Higher values zoom out further.
+    pub fn with_curvature(mut self, curvature: f64) -> Self {
+        self.path = FlyToPath::new(self.from, self.to, curvature);
+        self
+    }
+
+    pub fn from(&self) -> Viewport {
+        self.from
+    }
+
+    pub fn to(&self) -> Viewport {
+        self.to
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    pub fn is_finished(&self, elapsed: Duration) -> bool {
+        elapsed >= self.duration
+    }
+
+    /// Returns the view at the given time since the start of the animation
+    pub fn viewport_at(&self, elapsed: Duration) -> Viewport {
+        if self.is_finished(elapsed) {
+            return self.to;
+        }
+
+        let t = elapsed.as_secs_f32() / self.duration.as_secs_f32();
+        let (distance, width) = self.path.sample(self.easing.apply(t) as f64);
+
+        let direction = (
+            self.to.center.0 as f64 - self.from.center.0 as f64,
+            self.to.center.1 as f64 - self.from.center.1 as f64,
+        );
+        let progress = if self.path.distance > 0.0 {
+            distance / self.path.distance
+        } else {
+            0.0
+        };
+
+        Viewport {
+            center: (
+                (self.from.center.0 as f64 + direction.0 * progress) as f32,
+                (self.from.center.1 as f64 + direction.1 * progress) as f32,
+            ),
+            zoom_exponent: -(width / 2.0).log10() as f32,
+        }
+    }
+}
+
+/// Precomputed parameters of the zoom-and-pan path. All widths are full camera widths.
+#[derive(Copy, Clone, Debug)]
+struct FlyToPath {
+    rho: f64,
+    distance: f64,
+    w0: f64,
+    w1: f64,
+    r0: f64,
+    /// The total length of the path
+    length: f64,
+}
+
+impl FlyToPath {
+    fn new(from: Viewport, to: Viewport, rho: f64) -> Self {
+        let w0 = 2.0 * 10.0_f64.powf(-from.zoom_exponent as f64);
+        let w1 = 2.0 * 10.0_f64.powf(-to.zoom_exponent as f64);
+        let distance = (to.center.0 as f64 - from.center.0 as f64)
+            .hypot(to.center.1 as f64 - from.center.1 as f64);
+
+        // Without any panning this degenerates into a pure (logarithmic) zoom
+        if distance < 1e-12 * w0.min(w1) {
+            return Self {
+                rho,
+                distance: 0.0,
+                w0,
+                w1,
+                r0: 0.0,
+                length: (w1 / w0).ln().abs() / rho,
+            };
+        }
+
+        let rho2 = rho * rho;
+        let b0 = (w1 * w1 - w0 * w0 + rho2 * rho2 * distance * distance) / (2.0 * w0 * rho2 * distance);
+        let b1 = (w1 * w1 - w0 * w0 - rho2 * rho2 * distance * distance) / (2.0 * w1 * rho2 * distance);
+        let r0 = (-b0).asinh();
+        let r1 = (-b1).asinh();
+
+        Self {
+            rho,
+            distance,
+            w0,
+            w1,
+            r0,
+            length: (r1 - r0) / rho,
+        }
+    }
+
+    /// Returns the distance travelled and the camera width at the relative position `t` along the path
+    fn sample(&self, t: f64) -> (f64, f64) {
+        let s = t * self.length;
+
+        if self.distance == 0.0 {
+            let direction = if self.w1 < self.w0 { -1.0 } else { 1.0 };
+            return (0.0, self.w0 * (direction * self.rho * s).exp());
+        }
+
+        let rho2 = self.rho * self.rho;
+        let u = self.w0 / rho2 * self.r0.cosh() * (self.rho * s + self.r0).tanh()
+            - self.w0 / rho2 * self.r0.sinh();
+        let w = self.w0 * self.r0.cosh() / (self.rho * s + self.r0).cosh();
+
+        (u, w)
+    }
+}
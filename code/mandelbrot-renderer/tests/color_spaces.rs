@@ -0,0 +1,107 @@
+use std::f64::consts::PI;
+
+use mandelbrot_renderer::{
+    hsluv_to_linear_srgb, lab_to_lch, lch_to_lab, linear_srgb_to_hsluv, linear_srgb_to_oklab, oklab_to_linear_srgb,
+};
+
+/// 8-bit sRGB colors covering black, white, greys, the primaries and some mixed colors
+const COLORS: &[[u8; 3]] = &[
+    [0, 0, 0],
+    [255, 255, 255],
+    [128, 128, 128],
+    [1, 1, 1],
+    [255, 0, 0],
+    [0, 255, 0],
+    [0, 0, 255],
+    [255, 255, 0],
+    [0, 255, 255],
+    [255, 0, 255],
+    [18, 52, 86],
+    [250, 128, 114],
+    [75, 0, 130],
+    [200, 190, 10],
+];
+
+fn srgb_to_linear(color: [u8; 3]) -> [f64; 3] {
+    color.map(|channel| {
+        let value = channel as f64 / 255.0;
+        if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    })
+}
+
+fn linear_to_srgb(color: [f64; 3]) -> [u8; 3] {
+    color.map(|channel| {
+        let value = if channel <= 0.0031308 {
+            channel * 12.92
+        } else {
+            1.055 * channel.powf(1.0 / 2.4) - 0.055
+        };
+        (value.clamp(0.0, 1.0) * 255.0).round() as u8
+    })
+}
+
+fn assert_close(actual: [f64; 3], expected: [f64; 3], tolerance: f64) {
+    let close = actual
+        .iter()
+        .zip(expected)
+        .all(|(actual, expected)| (actual - expected).abs() <= tolerance);
+    assert!(close, "{actual:?} is not {expected:?}");
+}
+
+fn assert_round_trip(to_space: impl Fn([f64; 3]) -> [f64; 3], from_space: impl Fn([f64; 3]) -> [f64; 3]) {
+    for &color in COLORS {
+        let linear = srgb_to_linear(color);
+        let round_trip = from_space(to_space(linear));
+        assert_close(round_trip, linear, 1e-6);
+        assert_eq!(linear_to_srgb(round_trip), color);
+    }
+}
+
+#[test]
+fn oklab_round_trip() {
+    assert_round_trip(linear_srgb_to_oklab, oklab_to_linear_srgb);
+}
+
+#[test]
+fn oklch_round_trip() {
+    assert_round_trip(
+        |color| lab_to_lch(linear_srgb_to_oklab(color)),
+        |color| oklab_to_linear_srgb(lch_to_lab(color)),
+    );
+}
+
+#[test]
+fn hsluv_round_trip() {
+    assert_round_trip(linear_srgb_to_hsluv, hsluv_to_linear_srgb);
+}
+
+#[test]
+fn oklab_reference_values() {
+    let white = linear_srgb_to_oklab([1.0; 3]);
+    assert_close(white, [1.0, 0.0, 0.0], 1e-4);
+
+    let red = linear_srgb_to_oklab([1.0, 0.0, 0.0]);
+    assert_close(red, [0.627_955, 0.224_863, 0.125_846], 1e-5);
+
+    // OKLCh has the hue in radians
+    let [lightness, chroma, hue] = lab_to_lch(red);
+    assert_close([lightness, chroma, hue.to_degrees()], [0.627_955, 0.257_683, 29.233_885], 1e-4);
+}
+
+#[test]
+fn hsluv_reference_values() {
+    // Lightness and saturation go from 0 to 100, and the hue is in radians
+    let [lightness, saturation, hue] = linear_srgb_to_hsluv([1.0, 0.0, 0.0]);
+    assert_close([lightness, saturation, hue.to_degrees()], [53.237, 100.0, 12.177], 1e-3);
+
+    let [lightness, saturation, hue] = linear_srgb_to_hsluv([0.0, 0.0, 1.0]);
+    assert_close([lightness, saturation, hue.to_degrees() + 360.0], [32.301, 100.0, 265.874], 1e-3);
+
+    assert_close(linear_srgb_to_hsluv([1.0; 3]), [100.0, 0.0, 0.0], 1e-9);
+    assert_close(linear_srgb_to_hsluv([0.0; 3]), [0.0, 0.0, 0.0], 1e-9);
+    assert_close(hsluv_to_linear_srgb([53.237, 100.0, 12.177 / 180.0 * PI]), [1.0, 0.0, 0.0], 1e-3);
+}
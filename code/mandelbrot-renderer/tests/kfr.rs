@@ -0,0 +1,58 @@
+use mandelbrot_renderer::KfrLocation;
+
+const LOCATION: &str = "\
+Re: -1.7687788000785244330574853926457844166
+Im: -0.0017388311079356643839935185932785018
+Zoom: 2.5E1234
+Iterations: 12000
+Colors: 255,255,255,128,0,64,0,0,0,
+ColorOffset: 2
+Slopes: 1
+SlopePower: 50
+";
+
+fn location_with_color_offset(color_offset: &str) -> anyhow::Result<KfrLocation> {
+    KfrLocation::parse(&format!("Re: 0\nIm: 0\nZoom: 1\nColors: 255,0,0,0,255,0,0,0,255,\nColorOffset: {color_offset}\n"))
+}
+
+#[test]
+fn write_and_parse_round_trip() {
+    let location = KfrLocation::parse(LOCATION).unwrap();
+    assert_eq!(location.re, "-1.7687788000785244330574853926457844166");
+    assert_eq!(location.iterations, 12000);
+    assert_eq!(location.colors, [[255, 255, 255], [128, 0, 64], [0, 0, 0]]);
+    assert_eq!(location.color_offset, 2);
+
+    let mut written = Vec::new();
+    location.write(&mut written).unwrap();
+    let written = String::from_utf8(written).unwrap();
+    assert_eq!(written, LOCATION);
+
+    let parsed = KfrLocation::parse(&written).unwrap();
+    assert!((parsed.zoom_exponent - location.zoom_exponent).abs() < 1e-9);
+    assert_eq!(
+        parsed,
+        KfrLocation {
+            zoom_exponent: parsed.zoom_exponent,
+            ..location
+        }
+    );
+}
+
+#[test]
+fn reduces_large_color_offset() {
+    // The offset wraps around the three colors
+    let palette = location_with_color_offset("1").unwrap().palette().unwrap();
+    assert_ne!(location_with_color_offset("0").unwrap().palette().unwrap(), palette);
+    assert_eq!(location_with_color_offset("4").unwrap().palette().unwrap(), palette);
+    assert_eq!(
+        location_with_color_offset(&(usize::MAX - 1).to_string()).unwrap().palette().unwrap(),
+        location_with_color_offset(&((usize::MAX - 1) % 3).to_string()).unwrap().palette().unwrap()
+    );
+}
+
+#[test]
+fn rejects_negative_color_offset() {
+    let err = location_with_color_offset("-1").unwrap_err();
+    assert!(format!("{err:#}").contains("Invalid value `-1` for `ColorOffset`"), "{err:#}");
+}
@@ -0,0 +1,77 @@
+use mandelbrot_renderer::{atom_domain_period, find_misiurewicz_point, find_nucleus, nucleus_size};
+
+fn assert_close(actual: (f64, f64), expected: (f64, f64)) {
+    let distance = (actual.0 - expected.0).hypot(actual.1 - expected.1);
+    assert!(distance < 1e-12, "{actual:?} is not {expected:?}");
+}
+
+#[test]
+fn finds_nuclei() {
+    let nucleus = find_nucleus((-1.76, 0.0), 3, 64).expect("the period 3 minibrot on the real axis");
+    assert_close(nucleus.position, (-1.754_877_666_246_693, 0.0));
+    assert_eq!(nucleus.period, 3);
+
+    let nucleus = find_nucleus((-0.1, 0.7), 3, 64).expect("the period 3 bulb at the top of the main cardioid");
+    assert_close(nucleus.position, (-0.122_561_166_876_653_6, 0.744_861_766_619_744_2));
+
+    let nucleus = find_nucleus((-0.9, 0.1), 2, 64).expect("the period 2 bulb");
+    assert_close(nucleus.position, (-1.0, 0.0));
+}
+
+#[test]
+fn does_not_find_nuclei_of_lower_period() {
+    // The period 2 nucleus at -1 also solves z_4 = 0, but is divided out
+    assert!(find_nucleus((-1.0, 0.0), 4, 64).is_none());
+    assert!(find_nucleus((0.0, 0.0), 0, 64).is_none());
+}
+
+#[test]
+fn finds_misiurewicz_points() {
+    // The orbit of 0 for c = i is 0, i, -1 + i, -i, -1 + i, ...
+    let point = find_misiurewicz_point((0.05, 0.95), 2, 2, 64).expect("the tip of the filament at i");
+    assert_close(point.position, (0.0, 1.0));
+    assert_eq!((point.preperiod, point.period), (2, 2));
+
+    // The orbit of 0 for c = -2 is 0, -2, 2, 2, ...
+    let point = find_misiurewicz_point((-1.9, 0.0), 2, 1, 64).expect("the tip of the antenna at -2");
+    assert_close(point.position, (-2.0, 0.0));
+    assert_eq!((point.preperiod, point.period), (2, 1));
+
+    // Both are also periodic after a longer preperiod, which is divided out
+    assert!(find_misiurewicz_point((-2.0, 0.0), 3, 1, 64).is_none());
+    assert!(find_misiurewicz_point((0.0, 1.0), 3, 2, 64).is_none());
+    assert!(find_misiurewicz_point((0.0, 0.0), 2, 0, 64).is_none());
+}
+
+#[test]
+fn newton_gives_up_on_divergence() {
+    // Starting points far outside the set, or that are not numbers, must not loop or panic
+    assert!(find_nucleus((1000.0, 0.0), 3, 64).is_none());
+    assert!(find_nucleus((f64::NAN, 0.0), 3, 64).is_none());
+    // The orbit overflows to infinity
+    assert!(find_misiurewicz_point((1e200, 1e200), 3, 1, 64).is_none());
+    assert!(find_misiurewicz_point((0.0, f64::INFINITY), 2, 2, 64).is_none());
+
+    // Running out of steps before converging
+    assert!(find_nucleus((-1.76, 0.0), 3, 0).is_none());
+    assert!(find_nucleus((-1.76, 0.0), 3, 1).is_none());
+}
+
+#[test]
+fn estimates_atom_domain_period() {
+    assert_eq!(atom_domain_period((0.1, 0.1), 100), Some(1));
+    assert_eq!(atom_domain_period((-1.0, 0.0), 100), Some(2));
+    assert_eq!(atom_domain_period((-0.12, 0.74), 100), Some(3));
+    // Escapes right away
+    assert_eq!(atom_domain_period((3.0, 0.0), 100), None);
+}
+
+#[test]
+fn estimates_nucleus_size() {
+    // Relative to the main cardioid
+    assert_eq!(nucleus_size((0.0, 0.0), 1), 1.0);
+    assert!((nucleus_size((-1.0, 0.0), 2) - 0.5).abs() < 1e-12);
+
+    let size = nucleus_size((-1.754_877_666_246_693, 0.0), 3);
+    assert!((0.018..0.020).contains(&size), "{size}");
+}
@@ -0,0 +1,98 @@
+use mandelbrot_renderer::Palette;
+
+/// Encodes the linear colors of a palette back into the 8-bit sRGB values of the file
+fn srgb_entries(palette: &Palette) -> Vec<[u8; 3]> {
+    palette
+        .colors()
+        .iter()
+        .map(|color| {
+            color.map(|value| {
+                let value = if value <= 0.0031308 {
+                    value * 12.92
+                } else {
+                    1.055 * value.powf(1.0 / 2.4) - 0.055
+                };
+                (value * 255.0).round() as u8
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn ggr() {
+    let palette = Palette::parse_ggr(include_str!("palettes/sunrise.ggr"), 11).unwrap();
+    assert_eq!(palette.name(), "Sunrise");
+
+    let entries = srgb_entries(&palette);
+    assert_eq!(entries.len(), 11);
+    // Linear blending from black to red, with the middle at a quarter
+    assert_eq!(entries[0], [0, 0, 0]);
+    assert_eq!(entries[1], [51, 0, 0]);
+    assert_eq!(entries[5], [255, 0, 0]);
+    // Counter-clockwise hue blending from red to yellow
+    assert_eq!(entries[7], [255, 102, 0]);
+    assert_eq!(entries[9], [255, 255, 0]);
+    // A step to white
+    assert_eq!(entries[10], [255, 255, 255]);
+}
+
+#[test]
+fn ggr_without_header() {
+    assert!(Palette::parse_ggr("0 0.5 1 0 0 0 1 1 1 1 1 0 0", 8).is_err());
+}
+
+#[test]
+fn map() {
+    let palette = Palette::parse_map("ice", include_str!("palettes/ice.map")).unwrap();
+    assert_eq!(palette.name(), "ice");
+
+    let entries = srgb_entries(&palette);
+    assert_eq!(entries.len(), 256);
+    // The comment after the first entry is ignored
+    assert_eq!(entries[0], [0, 0, 255]);
+    assert_eq!(entries[128], [64, 128, 255]);
+    assert_eq!(entries[255], [255, 255, 255]);
+}
+
+#[test]
+fn map_with_invalid_line() {
+    let error = Palette::parse_map("broken", "0 0 0\n1 2\n").unwrap_err();
+    assert!(error.to_string().contains("line 2"), "{error}");
+}
+
+#[test]
+fn ugr() {
+    let palettes = Palette::parse_ugr(include_str!("palettes/samples.ugr"), 8).unwrap();
+    assert_eq!(palettes.len(), 2);
+
+    assert_eq!(palettes[0].name(), "Fire and Ice");
+    let entries = srgb_entries(&palettes[0]);
+    assert_eq!(entries[0], [255, 0, 0]);
+    assert_eq!(entries[1], [255, 128, 0]);
+    assert_eq!(entries[2], [255, 255, 0]);
+    assert_eq!(entries[4], [255, 255, 255]);
+    assert_eq!(entries[6], [0, 0, 255]);
+    // Wraps around from the last control point to the first one
+    assert_eq!(entries[7], [128, 0, 128]);
+
+    // Negative indices wrap around as well
+    assert_eq!(palettes[1].name(), "Black and White");
+    let entries = srgb_entries(&palettes[1]);
+    assert_eq!(entries[0], [255, 255, 255]);
+    assert_eq!(entries[2], [128, 128, 128]);
+    assert_eq!(entries[4], [0, 0, 0]);
+    assert_eq!(entries[6], [128, 128, 128]);
+}
+
+#[test]
+fn load_by_extension() {
+    let directory = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/palettes");
+
+    assert_eq!(Palette::load(directory.join("sunrise.ggr")).unwrap().name(), "Sunrise");
+    assert_eq!(Palette::load(directory.join("ice.map")).unwrap().name(), "ice");
+    assert_eq!(Palette::load(directory.join("samples.ugr")).unwrap().name(), "Fire and Ice");
+    assert_eq!(
+        Palette::load(directory.join("samples.ugr")).unwrap().colors().len(),
+        Palette::DEFAULT_GRADIENT_LENGTH
+    );
+}
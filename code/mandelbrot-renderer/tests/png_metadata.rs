@@ -0,0 +1,35 @@
+use mandelbrot_renderer::{
+    read_png_view_descriptor, write_png, Fractal, PaletteInterpolation, Polynomial, ShaderConstants, StepFunction,
+    ViewDescriptor, Viewport,
+};
+
+fn view(palette: &str, step_function: Option<StepFunction>) -> ViewDescriptor {
+    ViewDescriptor {
+        viewport: Viewport::new((-0.75, 0.1), 2.5),
+        fractal: if step_function.is_some() { Fractal::Custom } else { Fractal::Mandelbrot },
+        polynomial: Polynomial::default(),
+        step_function,
+        palette: palette.to_owned(),
+        palette_interpolation: PaletteInterpolation::Oklch,
+        shader_constants: ShaderConstants::default(),
+    }
+}
+
+fn round_trip(view: &ViewDescriptor) -> ViewDescriptor {
+    let mut png = Vec::new();
+    write_png(&mut png, (1, 1), &[0, 0, 0, 255], view).unwrap();
+    read_png_view_descriptor(&png[..]).unwrap()
+}
+
+#[test]
+fn latin1_metadata_round_trips() {
+    let view = view("Blätter", None);
+    assert_eq!(round_trip(&view), view);
+}
+
+#[test]
+fn unicode_metadata_round_trips() {
+    let step_function = StepFunction::new("// z² + c → Spiegelung ✓\nreturn vec2(z.x * z.x - z.y * z.y, -2.0 * z.x * z.y) + c;").unwrap();
+    let view = view("Ночь 🌌", Some(step_function));
+    assert_eq!(round_trip(&view), view);
+}
@@ -0,0 +1,37 @@
+use mandelbrot_renderer::StepFunction;
+
+#[test]
+fn accepts_valid_body() {
+    let step_function = StepFunction::new("return complex_reciprocal(z * z + c);");
+    assert!(step_function.is_ok(), "{}", step_function.unwrap_err());
+
+    // Braces of blocks and comments inside the body are fine
+    let step_function = StepFunction::new("if (z.x > 0.0) { return z + c; } /* } */\n// }\nreturn z * z + c;");
+    assert!(step_function.is_ok(), "{}", step_function.unwrap_err());
+}
+
+#[test]
+fn rejects_unknown_identifier() {
+    let diagnostic = StepFunction::new("return z * w + c;").unwrap_err();
+    let location = diagnostic.labels[0].location.expect("the error is inside the body");
+    assert_eq!((location.line, location.column), (1, 12));
+}
+
+#[test]
+fn rejects_wrong_return_type() {
+    assert!(StepFunction::new("return z.x;").is_err());
+}
+
+#[test]
+fn rejects_missing_return() {
+    let diagnostic = StepFunction::new("let w = z * z + c;").unwrap_err();
+    assert!(diagnostic.message.contains("return"), "{diagnostic}");
+}
+
+#[test]
+fn rejects_closing_the_step_function() {
+    let body = "return z; }\n@group(0) @binding(9) var<uniform> evil: f32;\nfn extra() -> vec2<f32> { return vec2(0.0);";
+    let diagnostic = StepFunction::new(body).unwrap_err();
+    let location = diagnostic.labels[0].location.expect("the error is inside the body");
+    assert_eq!((location.line, location.column), (1, 11));
+}
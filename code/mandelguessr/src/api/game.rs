@@ -1,8 +1,17 @@
+use std::ops::Range;
+
 use leptos::{server, ServerFnError};
 use serde::{Deserialize, Serialize};
 
 use super::models::game::Game;
 
+/// The part of the complex plane the game takes place in
+pub const MANDELBROT_POSITION_BOUNDS: (Range<f32>, Range<f32>) = (-2.0..0.5, -1.2..1.2);
+
+/// How far the patterns that have to be found are zoomed in
+#[cfg(feature = "ssr")]
+const TARGET_ZOOM_EXPONENT_BOUNDS: Range<f32> = 1.0..4.0;
+
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct GameInfo {
     pub position: (f32, f32),
@@ -21,16 +30,50 @@ pub async fn start_game() -> Result<GameInfo, ServerFnError> {
         return Err(ServerFnError::new("Unauthorized"));
     };
 
-    let position = (
-        2.0 * rand::random::<f32>() - 1.0,
-        0.8 * rand::random::<f32>(),
-    );
-    let zoom_exponent: f32 = 1.5 * rand::random::<f32>() + 1.5;
+    Ok(random_target())
+}
 
-    Ok(GameInfo {
-        position,
-        zoom_exponent,
-    })
+/// Picks a minibrot or Misiurewicz point near a random position of the playing field,
+/// zoomed in so far that it fills a good part of the view
+#[cfg(feature = "ssr")]
+fn random_target() -> GameInfo {
+    use mandelbrot_renderer::{atom_domain_period, find_misiurewicz_point, find_nucleus};
+    use rand::Rng;
+
+    let mut rng = rand::thread_rng();
+    let (re, im) = MANDELBROT_POSITION_BOUNDS;
+
+    for _ in 0..100 {
+        let start = (rng.gen_range(re.clone()) as f64, rng.gen_range(im.clone()) as f64);
+
+        // The camera half-width at which the feature is nicely framed
+        let feature = if rng.gen_bool(0.5) {
+            atom_domain_period(start, 1000)
+                .and_then(|period| find_nucleus(start, period, 64))
+                .map(|nucleus| (nucleus.position, 3.0 * nucleus.size))
+        } else {
+            let (preperiod, period) = (rng.gen_range(2..=8), rng.gen_range(1..=3));
+            find_misiurewicz_point(start, preperiod, period, 64).map(|point| (point.position, point.size))
+        };
+        let Some((position, half_width)) = feature else {
+            continue;
+        };
+
+        let position = (position.0 as f32, position.1 as f32);
+        let zoom_exponent = -half_width.log10() as f32;
+        if re.contains(&position.0) && im.contains(&position.1) && TARGET_ZOOM_EXPONENT_BOUNDS.contains(&zoom_exponent) {
+            return GameInfo {
+                position,
+                zoom_exponent,
+            };
+        }
+    }
+
+    // Newton's method practically always finds something, but just in case
+    GameInfo {
+        position: (rng.gen_range(re), rng.gen_range(im)),
+        zoom_exponent: rng.gen_range(TARGET_ZOOM_EXPONENT_BOUNDS),
+    }
 }
 
 #[server(EndGameAction, "/api/end_game")]
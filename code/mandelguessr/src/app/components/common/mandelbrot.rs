@@ -1,14 +1,19 @@
-use leptos::ev::{scroll, MouseEvent, WheelEvent};
+use leptos::ev::{scroll, KeyboardEvent, PointerEvent, WheelEvent};
 use leptos::{
     component, create_action, create_effect, create_local_resource, create_multi_action,
     create_node_ref, create_rw_signal, create_server_action, html::Canvas, view, IntoView,
     RwSignal, SignalGet, SignalSet, SignalUpdate, SignalWith,
 };
-use leptos::{create_signal, spawn_local, HtmlElement, MaybeSignal, SignalGetUntracked};
-use mandelbrot_renderer::MandelbrotRenderer;
+use leptos::{spawn_local, Callable, Callback, store_value, HtmlElement, MaybeSignal, Signal, SignalGetUntracked};
+use leptos::{create_trigger, on_cleanup, request_animation_frame_with_handle, AnimationFrameRequestHandle, SignalUpdateUntracked};
+use mandelbrot_renderer::{
+    FlyTo, FpsCounter, Fractal, Hud, MandelbrotRenderer, Navigation, NavigationSteps, Palette, Polynomial, QualityPolicy,
+    RendererConfig, Viewport,
+};
 use tailwind_fuse::tw_join;
-use std::ops::RangeBounds;
+use std::ops::{Range, RangeBounds};
 use std::{ops::Deref, time::Duration};
+use web_time::Instant;
 
 // Arguments needed:
 // How to specify position & size? position: (f32, f32), size as zoom: f32
@@ -51,19 +56,54 @@ pub fn Mandelbrot<RX: MandelbrotBounds, RY: MandelbrotBounds, RZ: MandelbrotBoun
     zoom_exponent_bounds: MaybeSignal<RZ>,
     #[prop(optional, into)]
     scroll_sensitivity: Option<f32>,
+    // How far a key press pans, as a fraction of the width of the view
+    #[prop(optional, into)]
+    pan_step: Option<f32>,
+    // How much a key press changes the zoom exponent
+    #[prop(optional, into)]
+    zoom_step: Option<f32>,
+    // An animated camera transition to play. It is reset to `None` once it has finished or the user moves the camera.
+    #[prop(optional)]
+    fly_to: Option<RwSignal<Option<FlyTo>>>,
+    // The fractal to render, e.g. for themed rounds. Defaults to the Mandelbrot set.
+    #[prop(optional, into)]
+    fractal: MaybeSignal<Fractal>,
+    // The polynomial of `Fractal::Newton`
+    #[prop(optional, into)]
+    polynomial: MaybeSignal<Polynomial>,
+    // The colors, e.g. parsed from a gradient file with `Palette::parse_ggr`. Defaults to the built-in palette.
+    #[prop(optional, into)]
+    palette: MaybeSignal<Palette>,
+    // Shows an overview of this part of the complex plane in a corner, with the current view marked.
+    // Clicking the overview moves the view there.
+    #[prop(optional, into)]
+    minimap: Option<(Range<f32>, Range<f32>)>,
+    // Whether a HUD with the coordinates, zoom, iterations under the pointer and frame rate is shown.
+    // The H key toggles it. Without this signal, there is no HUD.
+    #[prop(optional)]
+    hud: Option<RwSignal<bool>>,
     #[prop(optional)]
     class: &'static str,
 ) -> impl IntoView {
     let canvas_ref = create_node_ref::<Canvas>();
 
     let scroll_sensitivity = scroll_sensitivity.unwrap_or(0.001);
+    let navigation_steps = NavigationSteps {
+        pan: pan_step.unwrap_or(NavigationSteps::default().pan),
+        zoom: zoom_step.unwrap_or(NavigationSteps::default().zoom),
+    };
+    // The view the reset key returns to
+    let home = Viewport::new(position.get_untracked(), zoom_exponent.get_untracked());
 
     let mandelbrot: RwSignal<Option<MandelbrotRenderer>> = create_rw_signal(None);
 
-    // Camera signals
-    let (is_mouse_down, set_is_mouse_down) = create_signal::<bool>(false);
-    let (previous_mouse_position, set_previous_mouse_position) = create_signal::<Option<(f32, f32)>>(None);
+    // Palettes are not `Copy`, unlike the `MaybeSignal` they are wrapped in
+    let palette = Signal::derive(move || palette.get());
+
+    let fly_to = fly_to.unwrap_or_else(|| create_rw_signal(None));
+    let fly_to_start = store_value::<Option<Instant>>(None);
 
+    // Camera signals
     let (camera_position, set_camera_position) = (position.read_only(), position.write_only());
     let (camera_size_exponent, set_camera_size_exponent) = (zoom_exponent.read_only(), zoom_exponent.write_only());
 
@@ -83,7 +123,16 @@ pub fn Mandelbrot<RX: MandelbrotBounds, RY: MandelbrotBounds, RZ: MandelbrotBoun
                 leptos::logging::log!("spawing local future");
                 let canvas = canvas.deref().clone();
                 let size = size.get_untracked();
-                let mut new_mandelbrot = MandelbrotRenderer::new_from_canvas((size.0, size.1), canvas, true)
+                // Keeps dragging and zooming responsive on integrated GPUs
+                let quality_policy = QualityPolicy::new()
+                    .with_interaction_scale(0.5)
+                    .with_target_frame_time(Duration::from_millis(33));
+                let config = RendererConfig::default()
+                    .with_quality_policy(quality_policy)
+                    .with_fractal(fractal.get_untracked())
+                    .with_polynomial(polynomial.get_untracked())
+                    .with_palette(palette.get_untracked());
+                let mut new_mandelbrot = MandelbrotRenderer::new_from_canvas((size.0, size.1), canvas, config)
                     .await
                     .unwrap();
 
@@ -97,19 +146,92 @@ pub fn Mandelbrot<RX: MandelbrotBounds, RY: MandelbrotBounds, RZ: MandelbrotBoun
         }
     });
 
-    // "Game loop"
+    // Restart the animation clock whenever a new animation is set
+    create_effect(move |_| {
+        let start = fly_to.with(Option::is_some).then(Instant::now);
+        fly_to_start.set_value(start);
+    });
+
+    // Frames are only drawn when something changed. At most one frame is requested at a time.
+    let frame_request = store_value::<Option<AnimationFrameRequestHandle>>(None);
+    // Requests another frame without any visible change, e.g. to replace a frame rendered at a lower resolution
+    let redraw = create_trigger();
+    // As frames are only drawn on demand, this drops while the view stands still
+    let fps_counter = store_value(FpsCounter::new());
+    let fps = create_rw_signal::<Option<f32>>(None);
+
+    let draw_frame = move || {
+        frame_request.set_value(None);
+        if let Some(new_fps) = fps_counter.try_update_value(FpsCounter::record_frame).flatten() {
+            fps.set(Some(new_fps));
+        }
+
+        // Moving the camera requests the next frame of the animation
+        if let (Some(animation), Some(start)) = (fly_to.get_untracked(), fly_to_start.get_value()) {
+            let elapsed = start.elapsed();
+            let viewport = animation.viewport_at(elapsed);
+            set_camera_position.set(viewport.center);
+            set_camera_size_exponent.set(viewport.zoom_exponent);
+
+            if animation.is_finished(elapsed) {
+                fly_to.set(None);
+            }
+        }
+
+        // Untracked, as rendering does not change anything the redraw effect depends on
+        let mut needs_full_resolution = false;
+        mandelbrot.update_untracked(|mandelbrot| {
+            if let Some(mandelbrot) = mandelbrot {
+                mandelbrot.render(camera_position.get_untracked(), camera_size());
+                needs_full_resolution = mandelbrot.render_scale() < 1.0;
+            }
+        });
+        if needs_full_resolution {
+            redraw.notify();
+        }
+    };
+
+    create_effect(move |_| {
+        // Everything that changes the picture
+        camera_position.track();
+        camera_size_exponent.track();
+        size.track();
+        fly_to.track();
+        mandelbrot.track();
+        redraw.track();
+
+        if frame_request.with_value(Option::is_none) {
+            match request_animation_frame_with_handle(draw_frame) {
+                Ok(handle) => frame_request.set_value(Some(handle)),
+                Err(err) => leptos::logging::error!("failed to request an animation frame: {err:?}"),
+            }
+        }
+    });
+
+    // Stop drawing once the component is unmounted, e.g. when navigating to another route
+    on_cleanup(move || {
+        if let Some(handle) = frame_request.get_value() {
+            handle.cancel();
+        }
+    });
+
+    create_effect(move |_| {
+        let (fractal, polynomial) = (fractal.get(), polynomial.get());
+        mandelbrot.update(|renderer| {
+            if let Some(renderer) = renderer {
+                renderer.set_fractal(fractal);
+                renderer.set_polynomial(polynomial);
+            }
+        });
+    });
+
     create_effect(move |_| {
-        let _handle = leptos::set_interval_with_handle(
-            move || {
-                mandelbrot.update(move |mandelbrot| {
-                    if let Some(mandelbrot) = mandelbrot {
-                        mandelbrot.render(camera_position.get(), camera_size());
-                    }
-                })
-            },
-            Duration::from_millis(16),
-        )
-        .unwrap();
+        let palette = palette.get();
+        mandelbrot.update(|renderer| {
+            if let Some(renderer) = renderer {
+                renderer.set_palette(palette);
+            }
+        });
     });
 
     create_effect(move |_| {
@@ -121,6 +243,79 @@ pub fn Mandelbrot<RX: MandelbrotBounds, RY: MandelbrotBounds, RZ: MandelbrotBoun
         });
     });
 
+    // The bounds are not `Copy`, unlike the signals they are wrapped in
+    let position_bounds = Signal::derive(move || position_bounds.get());
+    let zoom_exponent_bounds = Signal::derive(move || zoom_exponent_bounds.get());
+
+    let mark_interaction = move || {
+        mandelbrot.update(|renderer| {
+            if let Some(renderer) = renderer {
+                renderer.mark_interaction();
+            }
+        });
+    };
+
+    // Moves the camera by a distance in canvas pixels, within the position bounds
+    let pan_by = move |delta: (f32, f32)| {
+        // Normalize to 0..=1
+        let size = size.get();
+        let mut delta = (delta.0 / size.0 as f32, delta.1 / size.1 as f32);
+
+        // Apply necessary transformations to make up for different coordinate systems
+        delta = (2.0 * delta.0, -2.0 * delta.1);
+
+        // Distance moved is proportional to camera size
+        let camera_size = camera_size();
+        delta = (delta.0 * camera_size.0, delta.1 * camera_size.1);
+
+        set_camera_position.update(|camera_position| {
+            // Add delta
+            *camera_position = (camera_position.0 + delta.0, camera_position.1 + delta.1);
+
+            // Limit camera position to be inside specified bounds
+            let position_bounds = position_bounds.get();
+            *camera_position = (position_bounds.0.limit_value(camera_position.0), position_bounds.1.limit_value(camera_position.1));
+        });
+        mark_interaction();
+    };
+
+    // Changes the zoom exponent, keeping the point under a position in canvas pixels in place
+    let zoom_around = move |position: (f32, f32), zoom_exponent_delta: f32| {
+        // Cursor position in the same -1..=1 coordinate system the shader uses
+        let size = size.get();
+        let cursor = (
+            2.0 * position.0 / size.0 as f32 - 1.0,
+            1.0 - 2.0 * position.1 / size.1 as f32,
+        );
+
+        // The point on the complex plane that is currently under the cursor
+        let old_camera_size = camera_size();
+        let camera_position = camera_position.get();
+        let cursor_position = (
+            camera_position.0 + cursor.0 * old_camera_size.0,
+            camera_position.1 + cursor.1 * old_camera_size.1,
+        );
+
+        set_camera_size_exponent.update(|camera_size_exponent| {
+            let new_camera_size_exponent = *camera_size_exponent + zoom_exponent_delta;
+            *camera_size_exponent = zoom_exponent_bounds.get().limit_value(new_camera_size_exponent);
+        });
+
+        // Move the camera so that the same point stays under the cursor
+        let new_camera_size = camera_size();
+        set_camera_position.update(|camera_position| {
+            *camera_position = (
+                cursor_position.0 - cursor.0 * new_camera_size.0,
+                cursor_position.1 - cursor.1 * new_camera_size.1,
+            );
+
+            // Limit camera position to be inside specified bounds
+            let position_bounds = position_bounds.get();
+            *camera_position = (position_bounds.0.limit_value(camera_position.0), position_bounds.1.limit_value(camera_position.1));
+        });
+        mark_interaction();
+    };
+
     #[allow(unused)]
     let mut non_passive_wheel = leptos::ev::Custom::<leptos::ev::WheelEvent>::new("wheel");
     #[cfg(feature = "hydrate")] {
@@ -128,73 +323,296 @@ pub fn Mandelbrot<RX: MandelbrotBounds, RY: MandelbrotBounds, RZ: MandelbrotBoun
         options.passive(false);
         canvas_ref.on_load(move |canvas: HtmlElement<leptos::html::Canvas>| {
             let _ = canvas.on(non_passive_wheel, move |event| {
-                set_camera_size_exponent.update(|camera_size_exponent| {
-                    let mut new_camera_size_exponent = *camera_size_exponent;
-                    new_camera_size_exponent -= event.delta_y() as f32 * scroll_sensitivity;
-                    new_camera_size_exponent = zoom_exponent_bounds.get().limit_value(new_camera_size_exponent);
+                fly_to.set(None);
+
+                let position = (event.offset_x() as f32, event.offset_y() as f32);
+                zoom_around(position, -event.delta_y() as f32 * scroll_sensitivity);
 
-                    *camera_size_exponent = new_camera_size_exponent;
-                });
                 event.prevent_default();
                 event.stop_propagation();
             });
         });
     }
 
+    // The position of the pointer over the canvas in canvas pixels, for the HUD
+    let hover_position = create_rw_signal::<Option<(f32, f32)>>(None);
+
+    // Positions (in canvas pixels) of the mouse buttons and fingers that are pressed, by pointer id
+    let pointers = store_value::<Vec<(i32, (f32, f32))>>(Vec::new());
+    // When and where the last single finger touched the canvas, to detect double taps
+    let last_tap = store_value::<Option<(Instant, (f32, f32))>>(None);
+
+    let on_pointer_down = move |event: PointerEvent| {
+        if event.pointer_type() == "mouse" && event.button() != 0 { // Only the main mouse button
+            return;
+        }
+        fly_to.set(None);
+
+        // Keep receiving the moves of this pointer when it leaves the canvas
+        if let Some(canvas) = canvas_ref.get_untracked() {
+            let _ = canvas.set_pointer_capture(event.pointer_id());
+        }
+
+        let position = (event.offset_x() as f32, event.offset_y() as f32);
+        if event.pointer_type() == "touch" && pointers.with_value(Vec::is_empty) {
+            let is_double_tap = last_tap.get_value().is_some_and(|(time, last_position)| {
+                time.elapsed() < DOUBLE_TAP_INTERVAL
+                    && (position.0 - last_position.0).hypot(position.1 - last_position.1) < DOUBLE_TAP_DISTANCE
+            });
+
+            if is_double_tap {
+                zoom_around(position, DOUBLE_TAP_ZOOM);
+                last_tap.set_value(None);
+            } else {
+                last_tap.set_value(Some((Instant::now(), position)));
+            }
+        }
+
+        pointers.update_value(|pointers| pointers.push((event.pointer_id(), position)));
+    };
+
+    let on_pointer_move = move |event: PointerEvent| {
+        let position = (event.offset_x() as f32, event.offset_y() as f32);
+        if hud.is_some() {
+            hover_position.set(Some(position));
+        }
+        let old_pointers = pointers.get_value();
+        let Some(index) = old_pointers.iter().position(|(id, _)| *id == event.pointer_id()) else {
+            return; // Hovering
+        };
+        let mut new_pointers = old_pointers.clone();
+        new_pointers[index].1 = position;
+        pointers.set_value(new_pointers.clone());
+
+        match (&old_pointers[..], &new_pointers[..]) {
+            // One finger or the mouse drags the view along
+            ([(_, old)], [(_, new)]) => pan_by((old.0 - new.0, old.1 - new.1)),
+            // Two fingers pan with their midpoint, and zoom with their distance around it. Any others are ignored.
+            ([(_, old_a), (_, old_b), ..], [(_, new_a), (_, new_b), ..]) if index < 2 => {
+                let old_midpoint = ((old_a.0 + old_b.0) / 2.0, (old_a.1 + old_b.1) / 2.0);
+                let new_midpoint = ((new_a.0 + new_b.0) / 2.0, (new_a.1 + new_b.1) / 2.0);
+                pan_by((old_midpoint.0 - new_midpoint.0, old_midpoint.1 - new_midpoint.1));
+
+                let old_distance = (old_a.0 - old_b.0).hypot(old_a.1 - old_b.1);
+                let new_distance = (new_a.0 - new_b.0).hypot(new_a.1 - new_b.1);
+                if old_distance > 0.0 && new_distance > 0.0 {
+                    zoom_around(new_midpoint, (new_distance / old_distance).log10());
+                }
+            }
+            _ => {}
+        }
+    };
+
+    let on_pointer_up = move |event: PointerEvent| {
+        pointers.update_value(|pointers| pointers.retain(|(id, _)| *id != event.pointer_id()));
+    };
+
+    let on_key_down = move |event: KeyboardEvent| {
+        // Leave browser shortcuts like Ctrl and - alone
+        if event.ctrl_key() || event.meta_key() || event.alt_key() {
+            return;
+        }
+        if let (Some(hud), "h" | "H") = (hud, event.key().as_str()) {
+            hud.update(|hud| *hud = !*hud);
+            return;
+        }
+        let Some(navigation) = Navigation::from_key(&event.key()) else {
+            return;
+        };
+        // Arrow keys would scroll the page as well
+        event.prevent_default();
+
+        let viewport = Viewport::new(camera_position.get_untracked(), camera_size_exponent.get_untracked());
+        if navigation == Navigation::Reset {
+            fly_to.set(Some(viewport.fly_to(home)));
+            return;
+        }
+        fly_to.set(None);
+
+        let viewport = viewport.navigate(navigation, navigation_steps, home);
+        set_camera_size_exponent.set(zoom_exponent_bounds.get().limit_value(viewport.zoom_exponent));
+        let position_bounds = position_bounds.get();
+        set_camera_position.set((position_bounds.0.limit_value(viewport.center.0), position_bounds.1.limit_value(viewport.center.1)));
+        mark_interaction();
+    };
 
     let class = move || {
         let size = size.get();
-        tw_join!(class, "cursor-pointer active:cursor-move")
+        // `touch-none` keeps the browser from scrolling or zooming the page while the view is dragged or pinched
+        tw_join!(class, "cursor-pointer active:cursor-move touch-none focus-visible:outline focus-visible:outline-2 focus-visible:outline-offset-2")
     };
 
-    view! {
+    let canvas = view! {
         <canvas ref=canvas_ref class=class
-        on:mousedown=move |event: MouseEvent| {
-            if event.button() == 0 { // Main button
-                set_is_mouse_down.set(true);
-                // event.prevent_default();
-            }
-        }
-        on:mouseup=move |event: MouseEvent| {
-            if event.button() == 0 { // Main button
-                set_is_mouse_down.set(false);
-                // event.prevent_default();
+        // Focusable, so that the view can be moved with the keyboard
+        tabindex="0"
+        aria-label="Fractal view. Pan with the arrow keys or WASD, zoom with plus and minus, reset with 0."
+        on:keydown=on_key_down
+        on:pointerdown=on_pointer_down
+        on:pointermove=on_pointer_move
+        on:pointerup=on_pointer_up
+        on:pointercancel=on_pointer_up
+        on:pointerleave=move |_| hover_position.set(None)
+        width=move || format!("{}px", size.get().0) height=move || format!("{}px", size.get().1)> </canvas>
+    };
+
+    if minimap.is_none() && hud.is_none() {
+        return canvas.into_view();
+    }
+
+    // Jumps to the clicked point, as far as the position bounds allow
+    let on_minimap_select = move |point: (f32, f32)| {
+        fly_to.set(None);
+        let position_bounds = position_bounds.get();
+        set_camera_position.set((position_bounds.0.limit_value(point.0), position_bounds.1.limit_value(point.1)));
+        mark_interaction();
+    };
+    let minimap = minimap.map(|minimap_bounds| view! {
+        <Minimap
+            bounds=minimap_bounds
+            center=camera_position
+            camera_size=Signal::derive(camera_size)
+            fractal=fractal polynomial=polynomial palette=palette
+            on_select=on_minimap_select
+        />
+    });
+
+    let hud_text = move || {
+        let size = size.get();
+        let viewport = Viewport::new(camera_position.get(), camera_size_exponent.get());
+        let mut hud = Hud::new(viewport, size.0);
+
+        if let Some(position) = hover_position.get() {
+            // The point on the complex plane under the pointer, like in `zoom_around`
+            let camera_size = camera_size();
+            let pointer = (
+                viewport.center.0 + (2.0 * position.0 / size.0 as f32 - 1.0) * camera_size.0,
+                viewport.center.1 + (1.0 - 2.0 * position.1 / size.1 as f32) * camera_size.1,
+            );
+            let cpu_renderer = mandelbrot.with(|renderer| renderer.as_ref().map(MandelbrotRenderer::cpu_renderer));
+            if let Some(cpu_renderer) = cpu_renderer {
+                hud = hud.with_pointer((pointer.0 as f64, pointer.1 as f64), &cpu_renderer);
             }
         }
-        on:mouseleave=move |_| {
-            set_is_mouse_down.set(false);
+        if let Some(fps) = fps.get() {
+            hud = hud.with_fps(fps);
         }
-        on:mousemove=move |event: MouseEvent| {
-            let position: (f32, f32) = (event.client_x() as f32, event.client_y() as f32);
 
-            if is_mouse_down.get() {
-                if let Some(previous_mouse_position) = previous_mouse_position.get() {
-                    let mut delta = (previous_mouse_position.0 - position.0, previous_mouse_position.1 - position.1);
+        hud.lines().join("\n")
+    };
+    let hud = hud.map(|hud| move || hud.get().then(|| view! {
+        <div class="absolute bottom-2 left-2 pointer-events-none rounded-md bg-black/60 px-2 py-1 font-mono text-xs text-white whitespace-pre">
+            {hud_text}
+        </div>
+    }));
 
-                    // Normalize to 0..=1
-                    let size = size.get();
-                    delta = (delta.0 / size.0 as f32, delta.1 / size.1 as f32);
+    view! {
+        <div class="relative w-fit h-fit">
+            {canvas}
+            {minimap}
+            {hud}
+        </div>
+    }
+    .into_view()
+}
 
-                    // Apply necessary transformations to make up for different coordinate systems
-                    delta = (2.0 * delta.0, -2.0 * delta.1);
+/// The width of the minimap in pixels. The height follows from the aspect ratio of its bounds.
+const MINIMAP_WIDTH: u32 = 160;
+/// The smallest width and height of the rectangle marking the view, so that it stays visible when zoomed in far
+const MINIMAP_MIN_MARKER_SIZE: f32 = 4.0;
 
-                    // Distance moved is proportionhal to camera size
-                    let camera_size = camera_size();
-                    delta = (delta.0 * camera_size.0, delta.1 * camera_size.1);
+/// A small overview of `bounds` in the top right corner of the `Mandelbrot` component, with a rectangle marking its view
+#[component]
+fn Minimap(
+    bounds: (Range<f32>, Range<f32>),
+    // The center and the horizontal and vertical camera size of the view to mark
+    #[prop(into)] center: Signal<(f32, f32)>,
+    camera_size: Signal<(f32, f32)>,
+    fractal: MaybeSignal<Fractal>,
+    polynomial: MaybeSignal<Polynomial>,
+    palette: Signal<Palette>,
+    // Called with the point on the complex plane that was clicked
+    #[prop(into)] on_select: Callback<(f32, f32)>,
+) -> impl IntoView {
+    let canvas_ref = create_node_ref::<Canvas>();
+    let mandelbrot: RwSignal<Option<MandelbrotRenderer>> = create_rw_signal(None);
 
-                    set_camera_position.update(|camera_position| {
-                        // Add delta
-                        *camera_position = (camera_position.0 + delta.0, camera_position.1 + delta.1);
+    let (re, im) = bounds;
+    let span = (re.end - re.start, im.end - im.start);
+    let size = (MINIMAP_WIDTH, (MINIMAP_WIDTH as f32 * span.1 / span.0).round() as u32);
+    // The whole bounds, as the renderer expects them
+    let overview_center = ((re.start + re.end) / 2.0, (im.start + im.end) / 2.0);
+    let overview_size = (span.0 / 2.0, span.1 / 2.0);
+    // The top left corner, as the ranges themselves are not `Copy`
+    let origin = (re.start, im.end);
 
-                        // Limit camera position to be inside specified bounds
-                        let position_bounds = position_bounds.get();
-                        *camera_position = (position_bounds.0.limit_value(camera_position.0), position_bounds.1.limit_value(camera_position.1));
-                    });
-                }
-            }
+    // Create renderer. The overview only has to be rendered again when the colors or the fractal change.
+    #[cfg(target_arch = "wasm32")]
+    create_effect(move |_| {
+        if let Some(canvas) = canvas_ref.get() {
+            spawn_local(async move {
+                let canvas = canvas.deref().clone();
+                let config = RendererConfig::default()
+                    .with_fractal(fractal.get_untracked())
+                    .with_polynomial(polynomial.get_untracked())
+                    .with_palette(palette.get_untracked());
+                let mut new_mandelbrot = MandelbrotRenderer::new_from_canvas(size, canvas, config)
+                    .await
+                    .unwrap();
 
-            set_previous_mouse_position.set(Some(position));
+                new_mandelbrot.render(overview_center, overview_size);
+                mandelbrot.set(Some(new_mandelbrot));
+            });
         }
-        width=move || format!("{}px", size.get().0) height=move || format!("{}px", size.get().1)> </canvas>
+    });
+
+    create_effect(move |_| {
+        let (fractal, polynomial, palette) = (fractal.get(), polynomial.get(), palette.get());
+        mandelbrot.update(|renderer| {
+            if let Some(renderer) = renderer {
+                renderer.set_fractal(fractal);
+                renderer.set_polynomial(polynomial);
+                renderer.set_palette(palette);
+                renderer.render(overview_center, overview_size);
+            }
+        });
+    });
+
+    // The view in minimap pixels: left, top, width and height
+    let marker = move || {
+        let (center, camera_size) = (center.get(), camera_size.get());
+        let width = (2.0 * camera_size.0 / span.0 * size.0 as f32).max(MINIMAP_MIN_MARKER_SIZE);
+        let height = (2.0 * camera_size.1 / span.1 * size.1 as f32).max(MINIMAP_MIN_MARKER_SIZE);
+        let x = (center.0 - origin.0) / span.0 * size.0 as f32;
+        let y = (origin.1 - center.1) / span.1 * size.1 as f32;
+
+        (x - width / 2.0, y - height / 2.0, width, height)
+    };
+
+    view! {
+        <div class="absolute top-2 right-2 overflow-hidden rounded-md shadow-lg ring-1 ring-white/50"
+            style:width=format!("{}px", size.0) style:height=format!("{}px", size.1)>
+            <canvas ref=canvas_ref class="cursor-crosshair touch-none"
+                aria-label="Overview of the whole set. Click to move the view there."
+                on:pointerdown=move |event: PointerEvent| {
+                    let point = (
+                        origin.0 + event.offset_x() as f32 / size.0 as f32 * span.0,
+                        origin.1 - event.offset_y() as f32 / size.1 as f32 * span.1,
+                    );
+                    on_select.call(point);
+                    event.stop_propagation();
+                }
+                width=format!("{}px", size.0) height=format!("{}px", size.1)> </canvas>
+            <div class="absolute pointer-events-none border-2 border-white"
+                style:left=move || format!("{}px", marker().0) style:top=move || format!("{}px", marker().1)
+                style:width=move || format!("{}px", marker().2) style:height=move || format!("{}px", marker().3)> </div>
+        </div>
     }
 }
+
+/// The longest time between the two taps of a double tap
+const DOUBLE_TAP_INTERVAL: Duration = Duration::from_millis(300);
+/// How far apart (in canvas pixels) the two taps of a double tap may be
+const DOUBLE_TAP_DISTANCE: f32 = 30.0;
+/// How much a double tap zooms in, as a change of the zoom exponent
+const DOUBLE_TAP_ZOOM: f32 = 0.5;
@@ -1,10 +1,12 @@
-use std::ops::Range;
+use std::time::Duration;
 
 use leptos::{
     component, create_effect, create_local_resource, create_rw_signal, create_server_action, view, IntoView, SignalGet, SignalSet, Suspense
 };
 
-use crate::{api::game::{end_game, start_game, EndGameAction}, app::components::common::Mandelbrot};
+use mandelbrot_renderer::{FlyTo, Viewport};
+
+use crate::{api::game::{end_game, start_game, EndGameAction, MANDELBROT_POSITION_BOUNDS}, app::components::common::Mandelbrot};
 
 #[component]
 pub fn Game() -> impl IntoView {
@@ -19,6 +21,12 @@ pub fn Game() -> impl IntoView {
 
     let finder_position = create_rw_signal((0.0, 0.0));
     let finder_zoom_exponent = create_rw_signal(0.0);
+    // Hidden until toggled with the H key
+    let finder_hud = create_rw_signal(false);
+
+    let result_position = create_rw_signal((0.0, 0.0));
+    let result_zoom_exponent = create_rw_signal(0.0);
+    let result_fly_to = create_rw_signal(None::<FlyTo>);
 
     let score = create_rw_signal(None::<u32>);
 
@@ -37,17 +45,27 @@ pub fn Game() -> impl IntoView {
         {move || {
             if let Some(score_value) = score.get() {
                 view!{
-                    <div class="flex flex-row h-full justify-center mt-20 space-x-8">
-                        <h1 class="text-white text-3xl font-bold">"🎉 Du hast "{score_value}" Punkte erziehtl! 🎉"</h1>
-                        <br/>
-                        <button class="text-white rounded-full text-2xl font-bold bg-[#600070]" on:click=move |_| {
-                            score.set(None);
-                            game_info.refetch();
-                            finder_position.set((0.0, 0.0));
-                            finder_zoom_exponent.set(0.0);
-                        }>
-                            Nächstes Spiel
-                        </button>
+                    <div class="flex flex-col h-full items-center mt-20 space-y-8">
+                        <div class="flex flex-row justify-center space-x-8">
+                            <h1 class="text-white text-3xl font-bold">"🎉 Du hast "{score_value}" Punkte erziehtl! 🎉"</h1>
+                            <br/>
+                            <button class="text-white rounded-full text-2xl font-bold bg-[#600070]" on:click=move |_| {
+                                score.set(None);
+                                game_info.refetch();
+                                finder_position.set((0.0, 0.0));
+                                finder_zoom_exponent.set(0.0);
+                            }>
+                                Nächstes Spiel
+                            </button>
+                        </div>
+                        // Flies from the guessed position to the pattern that had to be found
+                        <Mandelbrot
+                            size=(800, 600) position=result_position zoom_exponent=result_zoom_exponent
+                            position_bounds=MANDELBROT_POSITION_BOUNDS
+                            zoom_exponent_bounds=(0.0..4.5)
+                            fly_to=result_fly_to
+                            class="rounded-lg shadow-lg"
+                        />
                     </div>
                 }
             } else {
@@ -78,6 +96,8 @@ pub fn Game() -> impl IntoView {
                                 size=(800, 600) position=finder_position zoom_exponent=finder_zoom_exponent
                                 position_bounds=MANDELBROT_POSITION_BOUNDS
                                 zoom_exponent_bounds=(0.0..4.5)
+                                minimap=MANDELBROT_POSITION_BOUNDS
+                                hud=finder_hud
                                 class="rounded-lg shadow-lg"
                             />
                             <button on:click=move |_| {
@@ -87,6 +107,13 @@ pub fn Game() -> impl IntoView {
                                 let position_delta = ((position.0 - finder_position.0).abs(), (position.1 - finder_position.1).abs());
                                 let distance = (position_delta.0.powf(2.0) + position_delta.1.powf(2.0)).powf(0.5);
                                 let game_score = (100.0 * 50.0_f32.powf(-distance)) as u32;
+
+                                let guess = Viewport::new(finder_position, finder_zoom_exponent.get());
+                                let target = Viewport::new(position, zoom_exponent.get());
+                                result_position.set(guess.center);
+                                result_zoom_exponent.set(guess.zoom_exponent);
+                                result_fly_to.set(Some(guess.fly_to(target).with_duration(Duration::from_secs(4))));
+
                                 score.set(Some(game_score));
                                 end_game_action.dispatch(EndGameAction { score: game_score });
                             } class="absolute top-[520px] left-[250px] bg-[#1AA404]/70 hover:bg-[#1AA404]/100 rounded-full w-[300px] px-4 py-2 font-bold text-white text-xl">
@@ -101,5 +128,3 @@ pub fn Game() -> impl IntoView {
         }}
     }
 }
-
-const MANDELBROT_POSITION_BOUNDS: (Range<f32>, Range<f32>) = (-2.0..0.5, -1.2..1.2);
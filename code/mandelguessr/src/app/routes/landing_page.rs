@@ -1,10 +1,53 @@
-use leptos::{component, create_resource, view, IntoView};
+use std::time::Duration;
+
+use leptos::{component, create_effect, create_resource, create_rw_signal, on_cleanup, store_value, view, IntervalHandle, IntoView, SignalGetUntracked, SignalSet};
 use leptos_router::A;
+use mandelbrot_renderer::{FlyTo, Viewport};
+
+use crate::app::components::common::Mandelbrot;
+
+/// Interesting places the attract-mode tour flies between
+const TOUR: &[Viewport] = &[
+    Viewport { center: (-0.5, 0.0), zoom_exponent: 0.0 },
+    Viewport { center: (-0.745, 0.113), zoom_exponent: 2.3 },
+    Viewport { center: (-0.088, 0.654), zoom_exponent: 2.0 },
+    Viewport { center: (0.282, 0.01), zoom_exponent: 2.0 },
+    Viewport { center: (-1.7549, 0.0), zoom_exponent: 1.9 },
+];
 
 #[component]
 pub fn LandingPage() -> impl IntoView {
     let link_class = "text-blue-700 underline";
 
+    let position = create_rw_signal(TOUR[0].center);
+    let zoom_exponent = create_rw_signal(TOUR[0].zoom_exponent);
+    let fly_to = create_rw_signal(None::<FlyTo>);
+
+    // Attract mode: Fly to the next place of the tour every few seconds
+    let next_tour_stop = store_value(1);
+    let tour_interval = store_value::<Option<IntervalHandle>>(None);
+    create_effect(move |_| {
+        let handle = leptos::set_interval_with_handle(
+            move || {
+                let current = Viewport::new(position.get_untracked(), zoom_exponent.get_untracked());
+                let target = TOUR[next_tour_stop.get_value()];
+                next_tour_stop.set_value((next_tour_stop.get_value() + 1) % TOUR.len());
+
+                fly_to.set(Some(current.fly_to(target).with_duration(Duration::from_secs(5))));
+            },
+            Duration::from_secs(8),
+        )
+        .unwrap();
+        tour_interval.set_value(Some(handle));
+    });
+
+    // Stop the tour once the page is left, as the signals it animates are disposed
+    on_cleanup(move || {
+        if let Some(handle) = tour_interval.get_value() {
+            handle.clear();
+        }
+    });
+
     view! {
         <div class="w-full h-40 p-12"> // header
             <img href="/header.png" class="w-full h-full"/>
@@ -13,10 +56,19 @@ pub fn LandingPage() -> impl IntoView {
                 <p>"It's like Geoguessr except it's 💩"</p>
             </hgroup>
         </div>
+        <div class="flex flex-row justify-center">
+            <Mandelbrot
+                size=(800, 600) position=position zoom_exponent=zoom_exponent
+                position_bounds=(.., ..)
+                zoom_exponent_bounds=(0.0..4.5)
+                fly_to=fly_to
+                class="rounded-lg shadow-lg"
+            />
+        </div>
         // <A href="/register" class=link_class>Registrieren</A>
         // <br/>
         // <A href="/login" class=link_class>Login</A>
         // <br/>
         // <A href="/leaderboard" class=link_class>Rangliste</A>
     }
-}
\ No newline at end of file
+}
@@ -1,7 +1,7 @@
 use std::{clone, env, str::FromStr, sync::Arc};
 
 use crate::{app::App, backend::state::AppState};
-use axum::Router;
+use axum::{routing::get, Router};
 use diesel::{r2d2::ConnectionManager, PgConnection};
 use fileserv::file_and_error_handler;
 use leptos::{get_configuration, provide_context};
@@ -12,6 +12,7 @@ use tracing::level_filters::LevelFilter;
 
 pub mod database;
 pub mod state;
+pub mod tiles;
 mod fileserv;
 
 pub async fn start_server() {
@@ -33,11 +34,13 @@ pub async fn start_server() {
         counter: Arc::default(),
         routes,
         database: Arc::new(database::create_connection_pool()),
+        tiles: Arc::new(tiles::TileServer::from_env().await),
     };
 
     // Build router
     let cloned_app_state = app_state.clone();
     let router = Router::new()
+        .route("/tiles/:z/:x/:y", get(tiles::serve_tile))
         .leptos_routes_with_context(
             &app_state,
             app_state.routes.clone(),
@@ -6,12 +6,15 @@ use axum::extract::FromRef;
 use leptos::{expect_context, use_context, LeptosOptions};
 use leptos_router::RouteListing;
 
+use super::tiles::TileServer;
+
 #[derive(Debug, Clone, FromRef)]
 pub struct AppState {
     pub leptos_options: LeptosOptions,
     pub counter: Arc<AtomicU32>,
     pub routes: Vec<RouteListing>,
     pub database: Arc<r2d2::Pool<ConnectionManager<PgConnection>>>,
+    pub tiles: Arc<TileServer>,
 }
 
 impl AppState {
@@ -0,0 +1,396 @@
+//! Slippy-map tiles of the fractal at `/tiles/{z}/{x}/{y}.png`, for clients without WebGL and link previews.
+//!
+//! Tile `0/0/0` is the square around [`MANDELBROT_POSITION_BOUNDS`], the part of the complex plane the game uses.
+//! Every zoom level splits each tile into four, and `y` grows downwards, as in other slippy maps.
+//!
+//! Rendered tiles are kept in an on-disk LRU cache. Delete the cache directory after changing how tiles are rendered.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fmt,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use axum::{
+    extract::{Path, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use mandelbrot_renderer::{
+    write_png, CpuRenderer, MandelbrotRenderer, RendererConfig, ShaderConstants, TiledExport, ViewDescriptor,
+    Viewport,
+};
+
+use crate::api::game::MANDELBROT_POSITION_BOUNDS;
+
+/// The width and height of a tile in pixels
+pub const TILE_SIZE: u32 = 256;
+
+/// The number of cached tiles, unless `TILE_CACHE_CAPACITY` says otherwise
+const DEFAULT_CACHE_CAPACITY: usize = 10_000;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct TileKey {
+    z: u32,
+    x: u32,
+    y: u32,
+}
+
+impl TileKey {
+    /// The center and half size of the tile on the complex plane
+    fn camera(&self) -> ((f64, f64), f64) {
+        let (re, im) = MANDELBROT_POSITION_BOUNDS;
+        let world_size = (re.end - re.start).max(im.end - im.start) as f64;
+        let world_center = ((re.start + re.end) as f64 / 2.0, (im.start + im.end) as f64 / 2.0);
+
+        let tile_size = world_size / 2.0_f64.powi(self.z as i32);
+        let center = (
+            world_center.0 - world_size / 2.0 + (self.x as f64 + 0.5) * tile_size,
+            world_center.1 + world_size / 2.0 - (self.y as f64 + 0.5) * tile_size,
+        );
+
+        (center, tile_size / 2.0)
+    }
+
+    fn viewport(&self) -> Viewport {
+        let (center, half_size) = self.camera();
+        Viewport::new((center.0 as f32, center.1 as f32), -half_size.log10() as f32)
+    }
+}
+
+/// Renders tiles and caches them
+#[derive(Debug)]
+pub struct TileServer {
+    renderer: TileRenderer,
+    cache: TileCache,
+    /// Tiles that are being rendered, so that concurrent requests for the same tile share one render
+    in_flight: Mutex<HashMap<TileKey, Arc<tokio::sync::OnceCell<Vec<u8>>>>>,
+}
+
+enum TileRenderer {
+    /// Calculates in `f64`, so it can zoom in further
+    Cpu(CpuRenderer),
+    /// Reading frames back blocks until the GPU is done, so it is only used on blocking threads
+    Gpu(Arc<Mutex<MandelbrotRenderer>>),
+}
+
+impl fmt::Debug for TileRenderer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TileRenderer::Cpu(_) => f.write_str("Cpu"),
+            TileRenderer::Gpu(_) => f.write_str("Gpu"),
+        }
+    }
+}
+
+impl TileServer {
+    /// Configured by the environment variables
+    /// - `TILE_RENDERER`: `cpu` (default) or `gpu`. Falls back to the CPU if no graphics adapter is available.
+    /// - `TILE_CACHE_DIR`: Where rendered tiles are stored, defaults to `tile-cache`
+    /// - `TILE_CACHE_CAPACITY`: The maximum number of cached tiles, defaults to 10000
+    pub async fn from_env() -> Self {
+        let shader_constants = ShaderConstants::default();
+
+        let renderer = match env::var("TILE_RENDERER").as_deref() {
+            Ok("gpu") => {
+                let config = RendererConfig::new().with_shader_constants(shader_constants);
+                match MandelbrotRenderer::new_headless(config).await {
+                    Ok(renderer) => TileRenderer::Gpu(Arc::new(Mutex::new(renderer))),
+                    Err(error) => {
+                        tracing::warn!("Rendering tiles on the CPU, as the GPU renderer is not available: {error:#}");
+                        TileRenderer::Cpu(CpuRenderer::new(shader_constants))
+                    }
+                }
+            }
+            _ => TileRenderer::Cpu(CpuRenderer::new(shader_constants)),
+        };
+
+        let directory = env::var("TILE_CACHE_DIR").unwrap_or_else(|_| "tile-cache".to_owned());
+        let capacity = match env::var("TILE_CACHE_CAPACITY") {
+            Ok(capacity) => capacity.parse().unwrap_or_else(|_| {
+                tracing::warn!("Caching {DEFAULT_CACHE_CAPACITY} tiles, as TILE_CACHE_CAPACITY `{capacity}` is invalid");
+                DEFAULT_CACHE_CAPACITY
+            }),
+            Err(_) => DEFAULT_CACHE_CAPACITY,
+        };
+
+        Self {
+            renderer,
+            cache: TileCache::open(directory.into(), capacity).await,
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The deepest zoom level before tiles become blocky due to floating point precision
+    fn max_zoom(&self) -> u32 {
+        match self.renderer {
+            TileRenderer::Cpu(_) => 40,
+            TileRenderer::Gpu(_) => 14,
+        }
+    }
+
+    async fn tile(&self, key: TileKey) -> Result<Vec<u8>> {
+        if let Some(png) = self.cache.get(key).await {
+            return Ok(png);
+        }
+
+        let render = self.in_flight.lock().unwrap().entry(key).or_default().clone();
+        let png = render
+            .get_or_try_init(|| async {
+                // Another render of this tile may have finished since the cache was checked
+                if let Some(png) = self.cache.get(key).await {
+                    return Ok(png);
+                }
+
+                let png = self.render(key).await?;
+                self.cache.insert(key, &png).await;
+                anyhow::Ok(png)
+            })
+            .await
+            .cloned();
+
+        // Later requests find the tile in the cache. After a failure, the next request tries again.
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if in_flight.get(&key).is_some_and(|current| Arc::ptr_eq(current, &render)) {
+            in_flight.remove(&key);
+        }
+
+        png
+    }
+
+    async fn render(&self, key: TileKey) -> Result<Vec<u8>> {
+        match &self.renderer {
+            TileRenderer::Cpu(renderer) => {
+                let renderer = renderer.clone();
+                tokio::task::spawn_blocking(move || -> Result<Vec<u8>> {
+                    let (center, half_size) = key.camera();
+                    let pixels = renderer.render(center, (half_size, half_size), (TILE_SIZE, TILE_SIZE));
+
+                    let view = ViewDescriptor {
+                        viewport: key.viewport(),
+                        fractal: renderer.fractal(),
+                        polynomial: renderer.polynomial(),
+                        step_function: None,
+                        palette: renderer.palette().name().to_owned(),
+                        palette_interpolation: renderer.palette().interpolation(),
+                        shader_constants: renderer.shader_constants(),
+                    };
+                    let mut png = Vec::new();
+                    write_png(&mut png, (TILE_SIZE, TILE_SIZE), &pixels, &view)?;
+
+                    Ok(png)
+                })
+                .await?
+            }
+            TileRenderer::Gpu(renderer) => {
+                let renderer = renderer.clone();
+                let runtime = tokio::runtime::Handle::current();
+                tokio::task::spawn_blocking(move || -> Result<Vec<u8>> {
+                    let mut renderer = renderer.lock().unwrap();
+                    let mut png = Vec::new();
+                    runtime.block_on(
+                        TiledExport::new(key.viewport(), (TILE_SIZE, TILE_SIZE))
+                            .with_overlap(0)
+                            .write_png(&mut renderer, &mut png),
+                    )?;
+
+                    Ok(png)
+                })
+                .await?
+            }
+        }
+    }
+}
+
+/// Serves `/tiles/:z/:x/:y.png`
+pub async fn serve_tile(
+    State(tiles): State<Arc<TileServer>>,
+    Path((z, x, y)): Path<(u32, u32, String)>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(y) = y.strip_suffix(".png").and_then(|y| y.parse().ok()) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let tiles_per_side = 1_u64 << z.min(63);
+    if z > tiles.max_zoom() || x as u64 >= tiles_per_side || y as u64 >= tiles_per_side {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let png = match tiles.tile(TileKey { z, x, y }).await {
+        Ok(png) => png,
+        Err(error) => {
+            tracing::error!("Failed to render tile {z}/{x}/{y}: {error:#}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let etag = format!("\"{:x}\"", md5::compute(&png));
+    let is_cached_by_client = headers
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim().trim_start_matches("W/") == etag));
+    if is_cached_by_client {
+        return (StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response();
+    }
+
+    (
+        [
+            (CONTENT_TYPE, "image/png".to_owned()),
+            (CACHE_CONTROL, "public, max-age=86400".to_owned()),
+            (ETAG, etag),
+        ],
+        png,
+    )
+        .into_response()
+}
+
+/// Tiles stored as `<directory>/<z>/<x>/<y>.png`. The least recently used tiles are deleted once there are more than `capacity`.
+#[derive(Debug)]
+struct TileCache {
+    directory: PathBuf,
+    capacity: usize,
+    index: Mutex<LruIndex>,
+}
+
+/// Keeps track of the order tiles were last used in
+#[derive(Debug, Default)]
+struct LruIndex {
+    clock: u64,
+    last_used: HashMap<TileKey, u64>,
+    by_last_used: BTreeMap<u64, TileKey>,
+}
+
+impl LruIndex {
+    fn touch(&mut self, key: TileKey) {
+        if let Some(previous) = self.last_used.insert(key, self.clock) {
+            self.by_last_used.remove(&previous);
+        }
+        self.by_last_used.insert(self.clock, key);
+        self.clock += 1;
+    }
+
+    fn pop_least_recently_used(&mut self) -> Option<TileKey> {
+        let (_, key) = self.by_last_used.pop_first()?;
+        self.last_used.remove(&key);
+        Some(key)
+    }
+}
+
+impl TileCache {
+    /// Picks up the tiles that are already in the directory, ordered by their modification time
+    async fn open(directory: PathBuf, capacity: usize) -> Self {
+        // Scanning a large cache takes a while, which would block the runtime
+        let scan_directory = directory.clone();
+        let index = tokio::task::spawn_blocking(move || Self::scan(&scan_directory))
+            .await
+            .unwrap_or_default();
+        tracing::info!("Found {} cached tiles in {}", index.last_used.len(), directory.display());
+
+        Self {
+            directory,
+            capacity,
+            index: Mutex::new(index),
+        }
+    }
+
+    fn scan(directory: &std::path::Path) -> LruIndex {
+        let mut tiles = Vec::new();
+        for entry in walk_files(directory) {
+            let key = entry
+                .strip_prefix(directory)
+                .ok()
+                .and_then(|path| path.to_str())
+                .and_then(|path| {
+                    let mut parts = path.trim_end_matches(".png").split(std::path::MAIN_SEPARATOR);
+                    Some(TileKey {
+                        z: parts.next()?.parse().ok()?,
+                        x: parts.next()?.parse().ok()?,
+                        y: parts.next()?.parse().ok()?,
+                    })
+                });
+            let modified = entry.metadata().and_then(|metadata| metadata.modified());
+            if let (Some(key), Ok(modified)) = (key, modified) {
+                tiles.push((modified, key));
+            }
+        }
+        tiles.sort_by_key(|(modified, _)| *modified);
+
+        let mut index = LruIndex::default();
+        for (_, key) in tiles {
+            index.touch(key);
+        }
+
+        index
+    }
+
+    fn path(&self, key: TileKey) -> PathBuf {
+        self.directory
+            .join(key.z.to_string())
+            .join(key.x.to_string())
+            .join(format!("{}.png", key.y))
+    }
+
+    async fn get(&self, key: TileKey) -> Option<Vec<u8>> {
+        if !self.index.lock().unwrap().last_used.contains_key(&key) {
+            return None;
+        }
+
+        let png = tokio::fs::read(self.path(key)).await.ok()?;
+        self.index.lock().unwrap().touch(key);
+
+        Some(png)
+    }
+
+    async fn insert(&self, key: TileKey, png: &[u8]) {
+        let path = self.path(key);
+        // Written next to the tile and renamed, so that readers never see a partially written tile
+        let temporary_path = path.with_extension("png.tmp");
+        let written = match path.parent() {
+            Some(parent) => {
+                tokio::fs::create_dir_all(parent).await.is_ok()
+                    && tokio::fs::write(&temporary_path, png).await.is_ok()
+                    && tokio::fs::rename(&temporary_path, &path).await.is_ok()
+            }
+            None => false,
+        };
+        if !written {
+            let _ = tokio::fs::remove_file(&temporary_path).await;
+            tracing::warn!("Failed to cache tile at {}", path.display());
+            return;
+        }
+
+        let evicted: Vec<TileKey> = {
+            let mut index = self.index.lock().unwrap();
+            index.touch(key);
+            let excess = index.last_used.len().saturating_sub(self.capacity);
+            (0..excess).filter_map(|_| index.pop_least_recently_used()).collect()
+        };
+        for key in evicted {
+            let _ = tokio::fs::remove_file(self.path(key)).await;
+        }
+    }
+}
+
+/// All files below a directory, or none if it does not exist
+fn walk_files(directory: &std::path::Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .flat_map(|entry| {
+            let path = entry.path();
+            if path.is_dir() {
+                walk_files(&path)
+            } else {
+                vec![path]
+            }
+        })
+        .collect()
+}